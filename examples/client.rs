@@ -4,6 +4,7 @@ use azure_core::http::Method::{Delete, Get, Put};
 use azure_identity::DefaultAzureCredential;
 use bytes::Bytes;
 use clap::Parser;
+use std::collections::HashMap;
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
@@ -31,19 +32,21 @@ async fn main() -> Result<()> {
         credential,
         None,
     )?;
+    let query = HashMap::from([("api-version".to_string(), args.api_version.clone())]);
+    let headers = HashMap::new();
 
     let resp = client
-        .run(Put, &args.id, &args.api_version, args.body, None)
+        .run(Put, &args.id, &query, &headers, args.body, None)
         .await?;
     println!("PUT response: {}", String::from_utf8(resp.body.to_vec())?);
 
     let resp = client
-        .run(Get, &args.id, &args.api_version, None, None)
+        .run(Get, &args.id, &query, &headers, None, None)
         .await?;
     println!("GET response: {}", String::from_utf8(resp.body.to_vec())?);
 
     let resp = client
-        .run(Delete, &args.id, &args.api_version, None, None)
+        .run(Delete, &args.id, &query, &headers, None, None)
         .await?;
     println!(
         "DELETE response: {}",
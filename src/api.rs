@@ -8,20 +8,32 @@ use std::{path::PathBuf, sync::Arc};
 
 use std::str::FromStr;
 
-use crate::api::metadata_command::ConditionOpt;
+use crate::api::metadata_command::{ConditionOpt, Method, Operation};
+use crate::api::output::{Formatter, OutputFormat};
 use crate::cmd::{self, STDIN_OPTION};
 use crate::{
     api::{
         cli_expander::{CLIExpander, Shell},
-        invoke::OperationInvocation,
+        invoke::{BodyBuilder, OperationInvocation},
     },
     arg::CliInput,
-    client::Client,
+    client::{policies::SecretProps, poller::ProgressFunc, Client, NewClientOptions},
+    cloud::Cloud,
+    config::Config,
 };
+pub mod arg_type;
 pub mod cli_expander;
+pub mod editor;
+pub mod error;
+pub mod hcl_eval;
 pub mod invoke;
 pub mod metadata_command;
 pub mod metadata_index;
+pub mod output;
+pub mod query;
+pub mod shorthand;
+pub mod skeleton;
+pub mod update;
 
 #[derive(Debug, Clone)]
 pub struct ApiManager {
@@ -37,14 +49,18 @@ impl ApiManager {
         subcommands: &Vec<String>,
         args: &CliInput,
         matches: &ArgMatches,
+        cloud: &Cloud,
+        config: &Config,
+        client_options: NewClientOptions,
         cred_func: CF,
         mut resp_func: RF,
+        progress_func: Option<ProgressFunc>,
     ) -> Result<()>
     where
-        CF: FnOnce() -> Result<Arc<dyn TokenCredential>>,
+        CF: FnOnce(&Cloud) -> Result<Arc<dyn TokenCredential>>,
         RF: FnMut(String) -> (),
     {
-        let cred = cred_func()?;
+        let cred = cred_func(cloud)?;
 
         // Locate the command metadata
         let command_file = self.index.locate_command_file(args)?;
@@ -68,24 +84,24 @@ impl ApiManager {
                 // Locate the operation
                 let condition_opt = ConditionOpt::new(Some(id.clone()), None);
                 let cmd_cond = cmd_metadata.build_condition(condition_opt);
-                let operation = cmd_metadata
-                .select_operation_by_cond(cmd_cond.as_ref())
-                .ok_or(anyhow!(
-                        "failed to select the operation out from multiple operations available for this command based on the input"
-                ))?;
+                // For the generic update, the input object is the current resource to update.
+                let update_operations = cmd_metadata.select_update_operations(cmd_cond.as_ref());
+                let operation = match update_operations {
+                    Some((_, put_operation)) => put_operation,
+                    None => cmd_metadata
+                        .select_operation_by_cond(cmd_cond.as_ref())
+                        .ok_or(anyhow!(
+                            "failed to select the operation out from multiple operations available for this command based on the input"
+                        ))?,
+                };
 
                 let mut body = None;
                 if operation.is_put() {
                     obj.remove("id").unwrap();
                     let mut obj = serde_json::Value::Object(obj);
-                    if let Some(schema) = operation
-                        .http
-                        .as_ref()
-                        // The first response is the successful response
-                        .and_then(|http| http.responses.first())
-                        .and_then(|resp| resp.body.as_ref())
-                        .and_then(|b| b.json.schema.as_ref())
-                    {
+                    if update_operations.is_some() {
+                        obj = update_body(operation, matches, obj)?;
+                    } else if let Some(schema) = operation.response_schema() {
                         schema.shake_body(&mut obj)?;
                     }
                     body = Some(obj);
@@ -94,6 +110,11 @@ impl ApiManager {
                 // Print CLI and quit
                 if let Some(shell) = matches.get_one::<String>("print-cli").map(|v| v) {
                     let shell = Shell::from_str(shell.as_str())?;
+                    // The generic update is printed as is, i.e. with the edits rather than the
+                    // updated resource.
+                    if update_operations.is_some() {
+                        body = None;
+                    }
                     let expander = CLIExpander::new(
                         &shell,
                         &cmd_metadata.arg_groups,
@@ -111,80 +132,182 @@ impl ApiManager {
                 }
 
                 // Invoke the operation
-                let invoker = OperationInvocation::new(operation, &matches, &Some(id), &body);
+                let invoker = OperationInvocation::new(operation, &matches, &Some(id), &body)
+                    .with_progress(progress_func.clone());
                 let client = Client::new(
-                    "https://management.azure.com",
-                    vec!["https://management.azure.com/.default"],
+                    &cloud.arm_endpoint,
+                    vec![cloud.scope()],
                     cred.clone(),
-                    None,
+                    Some(NewClientOptions {
+                        secret_props: SecretProps::Paths(operation.secret_props()),
+                        ..client_options.clone()
+                    }),
                 )?;
-                resp_func(invoker.invoke(&client).await?);
+                invoke(&invoker, operation, &client, matches, &mut resp_func).await?;
             }
             return Ok(());
         }
 
         // Locate the operation (for metadata that contains multiple operations by conditions)
-        let name_args = cmd_metadata
-            .arg_groups
-            .iter()
-            .find(|ag| ag.name == "")
-            .and_then(|ag| {
-                Some(
-                    ag.args
-                        .iter()
-                        .filter(|arg| !arg.hide.unwrap_or(false))
-                        .filter(|arg| arg.id_part.is_some())
-                        .map(|arg| {
-                            (
-                                arg.var.clone(),
-                                matches.get_one::<String>(&arg.var).cloned(),
-                            )
-                        })
-                        .collect::<HashMap<_, _>>(),
-                )
-            });
+        // The configured defaults only fill in the absent id related args when there is no "--id".
+        let id_defaults = cmd_metadata.id_defaults(config);
+        let name_args = cmd_metadata.name_args(matches, &id_defaults);
         let id_arg = matches.get_one::<String>(cmd::ID_OPTION).cloned();
-        let condition_opt = ConditionOpt::new(id_arg, name_args);
+
+        // Output the request payload skeleton and quit.
+        // The skeleton is generated from the first operation that has a request body, as the
+        // payload doesn't vary by the condition.
+        if let Some(format) = matches
+            .try_get_one::<String>(cmd::GENERATE_SKELETON_OPTION)
+            .ok()
+            .flatten()
+        {
+            let schema = cmd_metadata
+                .operations
+                .iter()
+                .find_map(|op| op.schema_by_path(&[]))
+                .ok_or(anyhow!("no request payload found for this command"))?;
+            let skeleton = match format.as_str() {
+                "json" => serde_json::to_string_pretty(&skeleton::to_json(schema))?,
+                _ => skeleton::to_hcl(schema),
+            };
+            resp_func(skeleton);
+            return Ok(());
+        }
+
+        let condition_opt = ConditionOpt::new(id_arg.clone(), name_args);
         let cmd_cond = cmd_metadata.build_condition(condition_opt);
+
+        // The generic update, i.e. GET-modify-PUT
+        if let Some((get_operation, put_operation)) =
+            cmd_metadata.select_update_operations(cmd_cond.as_ref())
+        {
+            // Print CLI and quit
+            if let Some(shell) = matches.get_one::<String>("print-cli") {
+                let shell = Shell::from_str(shell.as_str())?;
+                let expander = CLIExpander::new(&shell, &cmd_metadata.arg_groups, args, None, None);
+                let mut cli = vec![];
+                cli.extend(subcommands.iter().cloned());
+                cli.extend(expander.expand()?);
+                resp_func(cli.join(" "));
+                return Ok(());
+            }
+
+            let mut secret_props = get_operation.secret_props();
+            secret_props.extend(put_operation.secret_props());
+            let client = Client::new(
+                &cloud.arm_endpoint,
+                vec![cloud.scope()],
+                cred,
+                Some(NewClientOptions {
+                    secret_props: SecretProps::Paths(secret_props),
+                    ..client_options
+                }),
+            )?;
+            let body = if let Some(p) = matches.get_one::<PathBuf>("file") {
+                // The payload read from the file is used as is, except for the read-only properties.
+                let mut body = read_payload(matches, &p)?;
+                if let Some(schema) = put_operation.response_schema() {
+                    schema.shake_body(&mut body)?;
+                }
+                body
+            } else {
+                let current =
+                    get_resource(get_operation, matches, &id_arg, &id_defaults, &client).await?;
+                let body = update_body(put_operation, matches, current)?;
+                if matches.get_flag("edit") {
+                    self.edit_resource(&body, matches, &command_file, cmd_cond.as_ref())?
+                } else {
+                    body
+                }
+            };
+
+            let invoker = OperationInvocation::new(put_operation, &matches, &id_arg, &Some(body))
+                .with_defaults(id_defaults)
+                .with_progress(progress_func);
+            invoke(&invoker, put_operation, &client, matches, &mut resp_func).await?;
+            return Ok(());
+        }
+
         let operation = cmd_metadata
                 .select_operation_by_cond(cmd_cond.as_ref())
                 .ok_or(anyhow!(
                     "failed to select the operation out from multiple operations available for this command based on the input"
                 ))?;
 
+        // The live resource to edit for the PATCH is got by the GET operation of the command.
+        let get_operation = if matches.get_flag("edit") && operation.is_patch() {
+            Some(
+                cmd_metadata
+                    .select_operation_by_method(cmd_cond.as_ref(), Method::Get)
+                    .ok_or(anyhow!(
+                        "no GET operation found for this command to get the resource to edit"
+                    ))?,
+            )
+        } else {
+            None
+        };
+        let mut secret_props = operation.secret_props();
+        if let Some(get_operation) = get_operation {
+            secret_props.extend(get_operation.secret_props());
+        }
+        let client = Client::new(
+            &cloud.arm_endpoint,
+            vec![cloud.scope()],
+            cred,
+            Some(NewClientOptions {
+                secret_props: SecretProps::Paths(secret_props),
+                ..client_options
+            }),
+        )?;
         let mut body = None;
         if operation.contains_request_body() {
             let mut hcl_body = None;
             if let Some(p) = matches.get_one::<PathBuf>("file") {
-                // Read the HCL from file
-                hcl_body = Some(get_file(&p)?);
+                // Read the payload from file, which might be exported from the live resource.
+                let mut v = read_payload(matches, &p)?;
+                if let Some(schema) = operation.response_schema() {
+                    schema.shake_body(&mut v)?;
+                }
+                body = Some(v);
+            } else if let Some(get_operation) = get_operation {
+                let mut current =
+                    get_resource(get_operation, matches, &id_arg, &id_defaults, &client).await?;
+                if let Some(schema) = get_operation.response_schema() {
+                    schema.shake_body(&mut current)?;
+                }
+                body = Some(self.edit_resource(
+                    &current,
+                    matches,
+                    &command_file,
+                    cmd_cond.as_ref(),
+                )?);
             } else if matches.get_flag("edit") {
-                // Read the HCL from editor
-                let header = "# ...".to_string();
+                // Read the HCL from editor, which starts from the skeleton of the request payload
+                let template = match operation.schema_by_path(&[]) {
+                    Some(schema) => skeleton::to_hcl(schema),
+                    None => "# ...".to_string(),
+                };
                 let content = edit(
-                    &header,
+                    &template,
                     self.root_path.to_string_lossy().as_ref(),
                     &command_file,
                     cmd_cond.as_ref(),
                 )?;
                 let content = content.trim();
 
-                // If the content is "empty", pause the process and exit.
+                // If the content is "empty" (or unchanged), pause the process and exit.
                 // This behavior is similar to "git commit".
-                if content == header || content.is_empty() {
+                if content == template.trim() || content.is_empty() {
                     bail!("Aborting due to empty body");
                 }
 
                 hcl_body = Some(content.to_string());
             }
 
-            body = if let Some(hcl_body) = hcl_body {
-                let body = hcl::parse(&hcl_body).context("parsing the file as HCL")?;
-                let v: serde_json::Value = hcl::from_body(body)?;
-                Some(v)
-            } else {
-                None
-            };
+            if let Some(hcl_body) = hcl_body {
+                body = Some(parse_hcl(&hcl_body, matches)?);
+            }
         }
 
         // Print CLI and quit
@@ -205,16 +328,184 @@ impl ApiManager {
             &matches,
             &matches.get_one::<String>(cmd::ID_OPTION).cloned(),
             &body,
-        );
-        let client = Client::new(
-            "https://management.azure.com",
-            vec!["https://management.azure.com/.default"],
-            cred,
-            None,
-        )?;
-        resp_func(invoker.invoke(&client).await?);
+        )
+        .with_defaults(id_defaults)
+        .with_progress(progress_func);
+        invoke(&invoker, operation, &client, matches, &mut resp_func).await?;
         return Ok(());
     }
+
+    // edit_resource opens the resource in the editor as HCL, and returns the edited resource once
+    // the changes are confirmed by the user.
+    fn edit_resource(
+        &self,
+        resource: &serde_json::Value,
+        matches: &ArgMatches,
+        command_file: &str,
+        cmd_cond: Option<&String>,
+    ) -> Result<serde_json::Value> {
+        // Fail before the editing, rather than when the changes are about to be confirmed.
+        editor::check_terminal()?;
+
+        // The top level properties that can't be HCL attributes are kept out of the editor, and put
+        // back to the edited resource as is.
+        let (resource, others) = editor::split(resource)?;
+        let original = editor::render(&resource)?;
+        let content = edit(
+            &original,
+            self.root_path.to_string_lossy().as_ref(),
+            command_file,
+            cmd_cond,
+        )?;
+        if content.trim().is_empty() {
+            bail!("Aborting due to empty body");
+        }
+        let mut edited = parse_hcl(&content, matches)?;
+
+        // The diff is based on the re-rendered content, so that the formatting changes are ignored.
+        let Some(diff) = editor::diff(&original, &editor::render(&edited)?) else {
+            bail!("Aborting due to no changes");
+        };
+        eprintln!("{diff}\n");
+        if !editor::confirm("Apply the changes?")? {
+            bail!("Aborting due to the changes not confirmed");
+        }
+        if let serde_json::Value::Object(map) = &mut edited {
+            map.extend(others);
+        }
+        Ok(edited)
+    }
+}
+
+// get_resource gets the current resource by the GET operation.
+async fn get_resource(
+    operation: &Operation,
+    matches: &ArgMatches,
+    id: &Option<String>,
+    id_defaults: &HashMap<String, String>,
+    client: &Client,
+) -> Result<serde_json::Value> {
+    let current = OperationInvocation::new(operation, matches, id, &None)
+        .with_defaults(id_defaults.clone())
+        .invoke(client)
+        .await
+        .context("getting the current resource")?;
+    serde_json::from_str(&current).context("parsing the current resource")
+}
+
+// read_payload reads the request payload from the file (or stdin for "-"), in the format specified
+// by "--file-format", or detected from the file extension. For stdin, it's detected from the content.
+fn read_payload(matches: &ArgMatches, p: &PathBuf) -> Result<serde_json::Value> {
+    let content = get_file(p)?;
+    let format = matches
+        .try_get_one::<String>(cmd::FILE_FORMAT_OPTION)
+        .ok()
+        .flatten()
+        .map(|v| v.as_str())
+        .or(p.extension().and_then(|ext| ext.to_str()));
+    match format {
+        Some("json") => serde_json::from_str(&content).context("parsing the file as JSON"),
+        Some("yaml" | "yml") => serde_yaml::from_str(&content).context("parsing the file as YAML"),
+        Some(_) => parse_hcl(&content, matches),
+        None if content.trim_start().starts_with('{') => {
+            serde_json::from_str(&content).context("parsing the file as JSON")
+        }
+        None => parse_hcl(&content, matches),
+    }
+}
+
+// parse_hcl parses the HCL payload, where the expressions are evaluated with the variables of
+// "--var" and "--var-file".
+fn parse_hcl(content: &str, matches: &ArgMatches) -> Result<serde_json::Value> {
+    let vars: Vec<String> = matches
+        .try_get_many::<String>(cmd::VAR_OPTION)
+        .ok()
+        .flatten()
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    let var_files: Vec<PathBuf> = matches
+        .try_get_many::<PathBuf>(cmd::VAR_FILE_OPTION)
+        .ok()
+        .flatten()
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    let ctx = hcl_eval::context(&vars, &var_files)?;
+    hcl::eval::from_str(content, &ctx).context("parsing the file as HCL")
+}
+
+// update_body builds the request body of the generic update from the current resource: the
+// read-only properties are removed, the body arguments (if any) are merged, and then the edits of
+// "--set", "--add" and "--remove" are applied.
+fn update_body(
+    operation: &Operation,
+    matches: &ArgMatches,
+    mut body: serde_json::Value,
+) -> Result<serde_json::Value> {
+    if let Some(schema) = operation.response_schema() {
+        schema.shake_body(&mut body)?;
+    }
+    let schema = operation.schema_by_path(&[]);
+    if let Some(schema) = schema {
+        let args = BodyBuilder(matches).build_body(schema)?;
+        update::merge(&mut body, args);
+    }
+    let edits = update::Edit::from_matches(matches, schema)?;
+    update::apply(&mut body, &edits)?;
+    Ok(body)
+}
+
+// invoke invokes the operation and passes the response to resp_func, either page by page (if
+// "--stream-pages" is specified) or as a whole. The response is filtered by the "--query", if any,
+// and then rendered in the "--output" format, if any.
+async fn invoke<RF>(
+    invoker: &OperationInvocation,
+    operation: &Operation,
+    client: &Client,
+    matches: &ArgMatches,
+    resp_func: &mut RF,
+) -> Result<()>
+where
+    RF: FnMut(String) -> (),
+{
+    let stream_pages = matches
+        .try_get_one::<bool>(cmd::STREAM_PAGES_OPTION)
+        .ok()
+        .flatten()
+        .cloned()
+        .unwrap_or(false);
+    let query = matches.get_one::<String>(cmd::QUERY_OPTION);
+    let format = matches
+        .get_one::<String>(cmd::OUTPUT_OPTION)
+        .map(|v| OutputFormat::from_str(v))
+        .transpose()?;
+    let no_wait = matches
+        .try_get_one::<bool>(cmd::NO_WAIT_OPTION)
+        .ok()
+        .flatten()
+        .cloned()
+        .unwrap_or(false);
+    // The response schema doesn't apply to the filtered response, or the LRO state.
+    let schema = if query.is_none() && !no_wait {
+        operation.response_schema()
+    } else {
+        None
+    };
+    let formatter = format.map_or_else(Formatter::raw, |format| Formatter::new(format, schema));
+    let mut output = |body: String| -> Result<()> {
+        let body = match query {
+            Some(query) => query::apply(query, &body)?,
+            None => body,
+        };
+        if let Some(body) = formatter.format(&body)? {
+            resp_func(body);
+        }
+        Ok(())
+    };
+    if stream_pages {
+        invoker.invoke_paged(client, output).await
+    } else {
+        output(invoker.invoke(client).await?)
+    }
 }
 
 #[cfg(any(feature = "embed-api", target_arch = "wasm32"))]
@@ -288,6 +579,9 @@ fn get_file(_: &PathBuf) -> Result<String> {
 
 #[cfg(not(target_arch = "wasm32"))]
 fn get_file(p: &PathBuf) -> Result<String> {
+    if p.as_os_str() == "-" {
+        return io::read_to_string(io::stdin()).context("reading from stdin");
+    }
     std::fs::read_to_string(p).context(format!("reading file from {p:?}"))
 }
 
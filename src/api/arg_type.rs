@@ -0,0 +1,215 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveTime};
+
+use super::shorthand;
+
+// ArgType is the type of a metadata argument (i.e. the "type" of the Arg or its property Schema),
+// which decides how the argument value is validated and converted to JSON.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ArgType {
+    Boolean,
+    Integer,
+    Float,
+    String,
+    DateTime,
+    Date,
+    Time,
+    Duration,
+    Array,
+    Object,
+    Unknown,
+}
+
+impl ArgType {
+    pub fn from_metadata(type_: &str) -> Self {
+        match type_ {
+            "boolean" | "bool" => Self::Boolean,
+            "dateTime" | "date-time" => Self::DateTime,
+            "date" => Self::Date,
+            "time" => Self::Time,
+            "duration" => Self::Duration,
+            "object" => Self::Object,
+            "string" | "uuid" | "password" | "byte" | "binary" | "ResourceId"
+            | "ResourceLocation" | "ResourceGroupName" | "SubscriptionId" => Self::String,
+            _ if type_.starts_with("integer") => Self::Integer,
+            _ if type_.starts_with("float") || type_ == "number" => Self::Float,
+            _ if type_.starts_with("array") => Self::Array,
+            _ if type_.starts_with("dict") || type_.starts_with('@') => Self::Object,
+            _ => Self::Unknown,
+        }
+    }
+
+    // value_name returns the placeholder of the argument value shown in the help message.
+    pub fn value_name(&self) -> &'static str {
+        match self {
+            Self::Boolean => "bool",
+            Self::Integer => "int",
+            Self::Float => "float",
+            Self::String => "string",
+            Self::DateTime => "date-time",
+            Self::Date => "date",
+            Self::Time => "time",
+            Self::Duration => "duration",
+            Self::Array => "array",
+            Self::Object => "object",
+            Self::Unknown => "value",
+        }
+    }
+
+    // validate is used as the clap value parser of the argument. The value is kept as a string,
+    // which is converted to JSON by to_json when building the request body.
+    pub fn validate(&self, value: &str) -> Result<String, String> {
+        self.to_json(value)
+            .map(|_| value.to_string())
+            .map_err(|e| format!("{e:#}"))
+    }
+
+    // to_json converts the argument value to its JSON form.
+    pub fn to_json(&self, value: &str) -> Result<serde_json::Value> {
+        let v = match self {
+            Self::Boolean => serde_json::Value::Bool(
+                value
+                    .parse::<bool>()
+                    .map_err(|_| anyhow!(r#"expect "true" or "false""#))?,
+            ),
+            Self::Integer => value.parse::<i64>().context("expect an integer")?.into(),
+            Self::Float => {
+                let f = value.parse::<f64>().context("expect a number")?;
+                serde_json::Number::from_f64(f)
+                    .ok_or(anyhow!("expect a finite number"))?
+                    .into()
+            }
+            Self::String => value.into(),
+            Self::DateTime => {
+                DateTime::parse_from_rfc3339(value)
+                    .context("expect an RFC 3339 date-time, e.g. 2006-01-02T15:04:05Z")?;
+                value.into()
+            }
+            Self::Date => {
+                NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .context("expect a date, e.g. 2006-01-02")?;
+                value.into()
+            }
+            Self::Time => {
+                NaiveTime::parse_from_str(value, "%H:%M:%S%.f")
+                    .context("expect a time, e.g. 15:04:05")?;
+                value.into()
+            }
+            Self::Duration => {
+                if !is_iso8601_duration(value) {
+                    return Err(anyhow!("expect an ISO 8601 duration, e.g. PT1H30M"));
+                }
+                value.into()
+            }
+            // The array and object values can be in either JSON or the shorthand syntax.
+            Self::Array => {
+                let v = shorthand::parse(value)?;
+                if !v.is_array() && !v.is_null() {
+                    return Err(anyhow!("expect an array"));
+                }
+                v
+            }
+            Self::Object => {
+                let v = shorthand::parse(value)?;
+                if !v.is_object() && !v.is_null() {
+                    return Err(anyhow!("expect an object"));
+                }
+                v
+            }
+            // The unknown types are firstly parsed as JSON, and fallback to a JSON string.
+            Self::Unknown => serde_json::from_str(value).unwrap_or(value.into()),
+        };
+        Ok(v)
+    }
+}
+
+// is_iso8601_duration checks whether the value is an ISO 8601 duration, e.g. "P1DT12H", "PT0.5S".
+fn is_iso8601_duration(value: &str) -> bool {
+    let Some(rest) = value.strip_prefix('P') else {
+        return false;
+    };
+    let (date, time) = match rest.split_once('T') {
+        Some((date, time)) => {
+            if time.is_empty() {
+                return false;
+            }
+            (date, Some(time))
+        }
+        None => (rest, None),
+    };
+    if date.is_empty() && time.is_none() {
+        return false;
+    }
+    let valid = |part: &str, units: &[char]| -> bool {
+        let mut units = units.iter();
+        let mut num = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() || c == '.' {
+                num.push(c);
+                continue;
+            }
+            // The units must be in order, each with a preceding number.
+            if num.is_empty() || num.parse::<f64>().is_err() || !units.any(|u| *u == c) {
+                return false;
+            }
+            num.clear();
+        }
+        num.is_empty()
+    };
+    valid(date, &['Y', 'M', 'W', 'D']) && time.is_none_or(|time| valid(time, &['H', 'M', 'S']))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn from_metadata() {
+        assert_eq!(ArgType::from_metadata("boolean"), ArgType::Boolean);
+        assert_eq!(ArgType::from_metadata("integer32"), ArgType::Integer);
+        assert_eq!(ArgType::from_metadata("float64"), ArgType::Float);
+        assert_eq!(ArgType::from_metadata("ResourceLocation"), ArgType::String);
+        assert_eq!(ArgType::from_metadata("array<string>"), ArgType::Array);
+        assert_eq!(ArgType::from_metadata("dict<string>"), ArgType::Object);
+        assert_eq!(ArgType::from_metadata("@Foo"), ArgType::Object);
+        assert_eq!(ArgType::from_metadata("foo"), ArgType::Unknown);
+    }
+
+    #[test]
+    fn to_json() {
+        assert_eq!(ArgType::Boolean.to_json("true").unwrap(), json!(true));
+        assert!(ArgType::Boolean.to_json("yes").is_err());
+        assert_eq!(ArgType::Integer.to_json("-1").unwrap(), json!(-1));
+        assert!(ArgType::Integer.to_json("1.5").is_err());
+        assert_eq!(ArgType::Float.to_json("1.5").unwrap(), json!(1.5));
+        assert_eq!(ArgType::String.to_json("123").unwrap(), json!("123"));
+        assert!(ArgType::DateTime.to_json("2006-01-02T15:04:05Z").is_ok());
+        assert!(ArgType::DateTime.to_json("2006-01-02").is_err());
+        assert!(ArgType::Date.to_json("2006-01-02").is_ok());
+        assert!(ArgType::Time.to_json("15:04:05").is_ok());
+        assert_eq!(ArgType::Array.to_json(r#"["a"]"#).unwrap(), json!(["a"]));
+        assert_eq!(ArgType::Array.to_json("[a,b]").unwrap(), json!(["a", "b"]));
+        assert!(ArgType::Array.to_json(r#"{}"#).is_err());
+        assert_eq!(
+            ArgType::Object.to_json(r#"{"a": 1}"#).unwrap(),
+            json!({"a": 1})
+        );
+        assert_eq!(ArgType::Object.to_json("a=b").unwrap(), json!({"a": "b"}));
+        assert_eq!(ArgType::Object.to_json("??").unwrap(), json!(null));
+        assert!(ArgType::Object.to_json("a").is_err());
+        assert_eq!(ArgType::Unknown.to_json("1").unwrap(), json!(1));
+        assert_eq!(ArgType::Unknown.to_json("a").unwrap(), json!("a"));
+    }
+
+    #[test]
+    fn iso8601_duration() {
+        for v in ["P1D", "PT1H30M", "P1Y2M3W4DT5H6M7.5S", "PT0.5S"] {
+            assert!(is_iso8601_duration(v), "{v}");
+        }
+        for v in ["", "P", "PT", "1D", "P1H", "PT1D", "PM", "P1D1Y"] {
+            assert!(!is_iso8601_duration(v), "{v}");
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use crate::{
@@ -6,8 +7,10 @@ use crate::{
 };
 
 use super::metadata_command::ArgGroup;
-use anyhow::{anyhow, bail, Context, Result};
+use super::shorthand;
+use anyhow::{anyhow, bail, Result};
 use clap::builder::PossibleValue;
+use serde_json::Value;
 
 pub struct CLIExpander {
     shell: Shell,
@@ -34,42 +37,51 @@ impl CLIExpander {
         }
     }
 
+    // expand expands the command line, where the body is expanded to the body args (including the
+    // nested ones). If any property of the body can't be expressed by the args, it falls back to
+    // the "--file" specified, or fails otherwise, rather than silently dropping it.
     pub fn expand(&self) -> Result<Vec<String>> {
         let mut cli_inputs = vec![];
+        let mut file = None;
         for (k, v) in &self.arg_input.opt_args() {
+            if ["file", "f"].contains(k) {
+                file = *v;
+            }
             if ["print-cli", "stdin", "edit", "e", "file", "f"].contains(k) {
                 continue;
             }
-            cli_inputs.push(arg::Arg::Optional(k.to_string(), v.map(String::from)));
+            cli_inputs.push(arg::Arg::Optional(
+                k.to_string(),
+                v.map(|v| self.shell.escape(&v.into())),
+            ));
         }
         if let Some(ref id) = self.id {
-            cli_inputs.push(arg::Arg::Optional(ID_OPTION.to_string(), Some(id.clone())));
+            cli_inputs.push(arg::Arg::Optional(
+                ID_OPTION.to_string(),
+                Some(self.shell.escape(&id.as_str().into())),
+            ));
         }
 
         if let Some(ref body) = self.body {
-            // Expand the root level parameters (except "properties"), plus the top level properties of the "properties".
-            self.arg_groups
-                .iter()
-                .skip_while(|ag| ag.name.is_empty())
-                .for_each(|ag| {
-                    ag.args.iter().for_each(|arg| {
-                        if let Some(prefix) = arg.var.split('.').next() {
-                            if prefix.starts_with("$")
-                                && prefix.to_lowercase().contains("parameters")
-                            {
-                                let paths: Vec<_> = arg.var.split('.').skip(1).collect();
-                                if let Ok(val) = self.find_value(&paths, body) {
-                                    if let Some(option_name) = arg.options.first() {
-                                        cli_inputs.push(arg::Arg::Optional(
-                                            option_name.clone(),
-                                            Some(self.shell.escape(val)),
-                                        ));
-                                    }
-                                }
-                            }
-                        }
-                    });
-                });
+            let Value::Object(map) = body else {
+                bail!("expect the body to be an object, got {body}");
+            };
+            let options = self.body_options();
+            let mut body_inputs = vec![];
+            let mut uncovered = vec![];
+            self.expand_object(&options, "", map, &mut body_inputs, &mut uncovered);
+            if uncovered.is_empty() {
+                cli_inputs.extend(body_inputs);
+            } else if let Some(file) = file {
+                cli_inputs.push(arg::Arg::Optional(
+                    "file".to_string(),
+                    Some(self.shell.escape(&file.into())),
+                ));
+            } else {
+                bail!(
+                    r#"the properties {uncovered:?} can't be expressed by the command line arguments, use "--file" instead"#
+                );
+            }
         }
         Ok(cli_inputs
             .iter()
@@ -77,36 +89,74 @@ impl CLIExpander {
             .collect::<Vec<_>>())
     }
 
-    fn find_value<'a>(
-        &'a self,
-        paths: &Vec<&str>,
-        val: &'a serde_json::Value,
-    ) -> Result<&'a serde_json::Value> {
-        let mut val = val;
-        for path in paths {
-            match val {
-                serde_json::Value::Null
-                | serde_json::Value::Bool(_)
-                | serde_json::Value::Number(_)
-                | serde_json::Value::String(_) => {
-                    bail!("looking up the field {path} on {val:?}");
-                }
-                serde_json::Value::Array(values) => {
-                    let idx: usize = path.parse().context(format!(
-                        "looking up the field {path} on an array: {values:?}"
-                    ))?;
-                    val = values.get(idx).context(format!(
-                        "getting the {idx}-th element on the array: {values:?}"
-                    ))?;
+    // body_options maps the body paths (e.g. "properties.securityProfile") to the options of the
+    // visible body args, including the nested ones.
+    fn body_options(&self) -> HashMap<String, String> {
+        let mut out = HashMap::new();
+        for ag in self.arg_groups.iter().filter(|ag| !ag.name.is_empty()) {
+            for arg in ag.all_args() {
+                if arg.hide.unwrap_or(false) {
+                    continue;
                 }
-                serde_json::Value::Object(map) => {
-                    val = map
-                        .get(*path)
-                        .context(format!("looking up the field {path} on an object {map:?}"))?;
+                let mut segments = arg.var.split('.');
+                let Some(prefix) = segments.next() else {
+                    continue;
+                };
+                if !prefix.starts_with("$") || !prefix.to_lowercase().contains("parameters") {
+                    continue;
                 }
+                // Prefer the long option
+                let Some(option) = arg
+                    .options
+                    .iter()
+                    .find(|opt| opt.len() > 1)
+                    .or(arg.options.first())
+                else {
+                    continue;
+                };
+                out.insert(segments.collect::<Vec<_>>().join("."), option.clone());
+            }
+        }
+        out
+    }
+
+    // expand_object expands the properties of the object to the args. The outermost arg covering
+    // a property wins, where the object and array values are expanded in the shorthand syntax.
+    // The properties not covered by any arg are collected to uncovered.
+    fn expand_object(
+        &self,
+        options: &HashMap<String, String>,
+        path: &str,
+        map: &serde_json::Map<String, Value>,
+        out: &mut Vec<arg::Arg>,
+        uncovered: &mut Vec<String>,
+    ) {
+        for (k, v) in map {
+            let path = if path.is_empty() {
+                k.clone()
+            } else {
+                format!("{path}.{k}")
+            };
+            // The null value is the same as the absent one.
+            if v.is_null() {
+                continue;
+            }
+            if let Some(option) = options.get(&path) {
+                let v = if v.is_object() || v.is_array() {
+                    &Value::String(shorthand::to_shorthand(v))
+                } else {
+                    v
+                };
+                out.push(arg::Arg::Optional(
+                    option.clone(),
+                    Some(self.shell.escape(v)),
+                ));
+            } else if let Value::Object(map) = v {
+                self.expand_object(options, &path, map, out, uncovered);
+            } else {
+                uncovered.push(path);
             }
         }
-        return Ok(val);
     }
 }
 
@@ -114,7 +164,12 @@ impl CLIExpander {
 pub enum Shell {
     Cmd,
     PowerShell,
+    // Unix quotes in double quotes, only escaping the double quotes. Prefer the specific shells.
     Unix,
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
 }
 
 impl Shell {
@@ -123,6 +178,10 @@ impl Shell {
             PossibleValue::new("cmd"),
             PossibleValue::new("powershell"),
             PossibleValue::new("unix"),
+            PossibleValue::new("bash"),
+            PossibleValue::new("zsh"),
+            PossibleValue::new("fish"),
+            PossibleValue::new("nushell"),
         ]
         .into_iter()
     }
@@ -158,6 +217,44 @@ impl Shell {
                 out.push('"');
                 out
             }
+            Shell::Bash | Shell::Zsh => {
+                // Nothing is special inside the single quotes, except the single quote itself.
+                let mut out = String::new();
+                out.push('\'');
+                for c in chars {
+                    if c == '\'' {
+                        out.push_str(r#"'\''"#); // escape `'` by `'\''`
+                    } else {
+                        out.push(c);
+                    }
+                }
+                out.push('\'');
+                out
+            }
+            Shell::Fish => {
+                let mut out = String::new();
+                out.push('\'');
+                for c in chars {
+                    if c == '\'' || c == '\\' {
+                        out.push('\\'); // escape `'` and `\` by `\'` and `\\`
+                    }
+                    out.push(c);
+                }
+                out.push('\'');
+                out
+            }
+            Shell::Nushell => {
+                let mut out = String::new();
+                out.push('"');
+                for c in chars {
+                    if c == '"' || c == '\\' {
+                        out.push('\\'); // escape `"` and `\` by `\"` and `\\`
+                    }
+                    out.push(c);
+                }
+                out.push('"');
+                out
+            }
         }
     }
 }
@@ -170,6 +267,10 @@ impl FromStr for Shell {
             "cmd" => Ok(Shell::Cmd),
             "powershell" => Ok(Shell::PowerShell),
             "unix" => Ok(Shell::Unix),
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            "nushell" => Ok(Shell::Nushell),
             _ => Err(anyhow!("invalid shell: {s}")),
         }
     }
@@ -276,4 +377,165 @@ mod tests {
             r#""foo""bar""#
         );
     }
+
+    #[test]
+    fn test_bash_escape_with_quote() {
+        assert_eq!(
+            Shell::Bash.escape(&serde_json::json!(r#"foo'"$bar"#)),
+            r#"'foo'\''"$bar'"#
+        );
+    }
+
+    #[test]
+    fn test_fish_escape_with_quote() {
+        assert_eq!(
+            Shell::Fish.escape(&serde_json::json!(r#"foo'\"bar"#)),
+            r#"'foo\'\\"bar'"#
+        );
+    }
+
+    #[test]
+    fn test_nushell_escape_with_quote() {
+        assert_eq!(
+            Shell::Nushell.escape(&serde_json::json!(r#"foo'\"bar"#)),
+            r#""foo'\\\"bar""#
+        );
+    }
+
+    fn arg_groups() -> Vec<ArgGroup> {
+        serde_json::from_str(
+            r#"
+[
+  {
+    "name": "",
+    "args": [
+      {"type": "ResourceGroupName", "var": "$Path.resourceGroupName", "options": ["g", "resource-group"]}
+    ]
+  },
+  {
+    "name": "Parameters",
+    "args": [
+      {"type": "ResourceLocation", "var": "$parameters.location", "options": ["l", "location"]},
+      {"type": "object", "var": "$parameters.tags", "options": ["tags"]},
+      {"type": "string", "var": "$parameters.properties.secret", "options": ["secret"], "hide": true}
+    ]
+  },
+  {
+    "name": "Properties",
+    "args": [
+      {
+        "type": "object",
+        "var": "$parameters.properties.securityProfile",
+        "options": ["security-profile"],
+        "args": [
+          {"type": "boolean", "var": "$parameters.properties.securityProfile.encryptionAtHost", "options": ["encryption-at-host"]}
+        ]
+      },
+      {"type": "integer32", "var": "$parameters.properties.count", "options": ["count"]},
+      {"type": "array<string>", "var": "$parameters.properties.zones", "options": ["zones"]},
+      {"type": "string", "var": "$parameters.properties.description", "options": ["description"]}
+    ]
+  }
+]
+"#,
+        )
+        .unwrap()
+    }
+
+    // bash_unquote reverses the bash quoting of the "--option=value" input.
+    fn bash_unquote(input: &str) -> String {
+        let mut out = String::new();
+        let mut quoted = false;
+        let mut chars = input.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => quoted = !quoted,
+                '\\' if !quoted => out.extend(chars.next()),
+                c => out.push(c),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_expand_round_trip() {
+        use crate::api::invoke::BodyBuilder;
+        use crate::api::metadata_command::Schema;
+
+        let schema: Schema = serde_json::from_str(
+            r#"
+{
+  "type": "object",
+  "props": [
+    {"type": "ResourceLocation", "name": "location", "arg": "$parameters.location"},
+    {"type": "object", "name": "tags", "arg": "$parameters.tags", "additionalProps": {"item": {"type": "string"}}},
+    {
+      "type": "object",
+      "name": "properties",
+      "props": [
+        {
+          "type": "object",
+          "name": "securityProfile",
+          "arg": "$parameters.properties.securityProfile",
+          "props": [
+            {"type": "boolean", "name": "encryptionAtHost", "arg": "$parameters.properties.securityProfile.encryptionAtHost"}
+          ]
+        },
+        {"type": "integer32", "name": "count", "arg": "$parameters.properties.count"},
+        {"type": "array<string>", "name": "zones", "arg": "$parameters.properties.zones", "item": {"type": "string"}},
+        {"type": "string", "name": "description", "arg": "$parameters.properties.description"}
+      ]
+    }
+  ]
+}
+"#,
+        )
+        .unwrap();
+        let body = serde_json::json!({
+            "location": "west us",
+            "tags": {"a b": "it's"},
+            "properties": {
+                "securityProfile": {"encryptionAtHost": true},
+                "count": 2,
+                "zones": ["1", "2"],
+                "description": r#"say "hi" to $HOME\"#,
+            },
+        });
+
+        let arg_groups = arg_groups();
+        let input = CliInput::new(Vec::<String>::new()).unwrap();
+        let expander =
+            CLIExpander::new(&Shell::Bash, &arg_groups, &input, Some(body.clone()), None);
+        let cli = expander.expand().unwrap();
+        assert!(cli.contains(&"--security-profile='{encryptionAtHost:true}'".to_string()));
+
+        let mut cmd = clap::Command::new("test");
+        for ag in &arg_groups {
+            for arg in ag.all_args() {
+                let long = arg.options.iter().find(|opt| opt.len() > 1).unwrap();
+                cmd = cmd.arg(clap::Arg::new(arg.var.clone()).long(long.clone()));
+            }
+        }
+        let matches = cmd.get_matches_from(
+            std::iter::once("test".to_string()).chain(cli.iter().map(|arg| bash_unquote(arg))),
+        );
+        assert_eq!(BodyBuilder(&matches).build_body(&schema).unwrap(), body);
+    }
+
+    #[test]
+    fn test_expand_uncovered() {
+        let arg_groups = arg_groups();
+        // The hidden args can't be specified from the command line.
+        let body = serde_json::json!({"location": "westus", "properties": {"secret": "foo"}});
+
+        let input = CliInput::new(Vec::<String>::new()).unwrap();
+        let expander =
+            CLIExpander::new(&Shell::Bash, &arg_groups, &input, Some(body.clone()), None);
+        assert!(expander.expand().is_err());
+
+        // Fallback to the "--file"
+        let input = CliInput::new(["--file", "body.json", "--print-cli", "bash"]).unwrap();
+        let expander = CLIExpander::new(&Shell::Bash, &arg_groups, &input, Some(body), None);
+        assert_eq!(expander.expand().unwrap(), vec!["--file='body.json'"]);
+    }
 }
@@ -0,0 +1,226 @@
+// The helpers to edit the live resource in the editor, as HCL.
+
+use std::io::{self, IsTerminal, Write};
+
+use anyhow::{Result, bail};
+use hcl_edit::expr::{Array, Expression, Object, ObjectKey, ObjectValue, ObjectValueTerminator};
+use hcl_edit::structure::{Attribute, Block, Body, Structure};
+use hcl_edit::{Decorate, Ident};
+use serde_json::{Map, Value};
+
+const INDENT: &str = "  ";
+
+// split splits the JSON object into the part that can be rendered as an HCL body, and the top level
+// properties whose keys aren't valid identifiers (e.g. "@odata.type"), which can't be HCL attributes.
+pub fn split(value: &Value) -> Result<(Value, Map<String, Value>)> {
+    let Value::Object(map) = value else {
+        bail!("expect an object, got {value}");
+    };
+    let (body, others): (Map<_, _>, Map<_, _>) =
+        map.clone().into_iter().partition(|(k, _)| is_identifier(k));
+    Ok((Value::Object(body), others))
+}
+
+// render renders the JSON object for editing, as HCL. The nested objects are rendered as blocks, as
+// long as their keys are valid identifiers, while the other values are rendered as attributes. The
+// top level keys must be valid identifiers, see split for the others.
+pub fn render(value: &Value) -> Result<String> {
+    let Value::Object(map) = value else {
+        bail!("expect an object, got {value}");
+    };
+    if let Some(k) = map.keys().find(|k| !is_identifier(k)) {
+        bail!(r#"the property "{k}" isn't a valid HCL identifier"#);
+    }
+    Ok(to_body(map, 0).to_string())
+}
+
+// to_body renders the object, whose keys are all valid identifiers, as the HCL body nested in the
+// blocks of the depth.
+fn to_body(map: &Map<String, Value>, depth: usize) -> Body {
+    let mut body = Body::new();
+    for (k, v) in map {
+        let mut structure: Structure = match v {
+            Value::Object(obj) if obj.keys().all(|k| is_identifier(k)) => {
+                let mut block = Block::new(Ident::new(k.as_str()));
+                block.body = to_body(obj, depth + 1);
+                if obj.is_empty() {
+                    block.body.set_prefer_oneline(true);
+                } else {
+                    block.body.decor_mut().set_suffix(INDENT.repeat(depth));
+                }
+                block.into()
+            }
+            v => Attribute::new(Ident::new(k.as_str()), to_expression(v, depth)).into(),
+        };
+        structure.decor_mut().set_prefix(INDENT.repeat(depth));
+        body.push(structure);
+    }
+    body
+}
+
+// to_expression renders the JSON value as the HCL expression of the attribute nested in the blocks of
+// the depth. The object items are put one per line.
+fn to_expression(value: &Value, depth: usize) -> Expression {
+    match value {
+        Value::Null => Expression::null(),
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => n.into(),
+            (_, Some(n)) => n.into(),
+            _ => n.as_f64().map_or_else(Expression::null, Expression::from),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(values) => Array::from(
+            values
+                .iter()
+                .map(|v| to_expression(v, depth))
+                .collect::<Vec<_>>(),
+        )
+        .into(),
+        Value::Object(map) => {
+            let mut object = Object::new();
+            for (i, (k, v)) in map.iter().enumerate() {
+                let mut key = if is_identifier(k) {
+                    ObjectKey::from(Ident::new(k.as_str()))
+                } else {
+                    ObjectKey::from(Expression::from(k.as_str()))
+                };
+                let indent = INDENT.repeat(depth + 1);
+                key.decor_mut().set_prefix(if i == 0 {
+                    format!("\n{indent}")
+                } else {
+                    indent
+                });
+                let mut value = ObjectValue::new(to_expression(v, depth + 1));
+                value.set_terminator(ObjectValueTerminator::Newline);
+                object.insert(key, value);
+            }
+            if !map.is_empty() {
+                object.set_trailing(INDENT.repeat(depth));
+            }
+            object.into()
+        }
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// diff returns the line diff from the old content to the new one, where the removed lines are
+// prefixed by "- " and the added lines by "+ ". None is returned if there is no difference.
+pub fn diff(old: &str, new: &str) -> Option<String> {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+    if old == new {
+        return None;
+    }
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    Some(out.join("\n"))
+}
+
+// check_terminal ensures the stdin is a terminal, which the confirmation is read from. Otherwise, it
+// is either consumed by e.g. "--file -", or the confirmation would block (e.g. in CI).
+pub fn check_terminal() -> Result<()> {
+    if !io::stdin().is_terminal() {
+        bail!("the changes can only be confirmed from a terminal");
+    }
+    Ok(())
+}
+
+// confirm asks the user for the confirmation from the terminal.
+pub fn confirm(prompt: &str) -> Result<bool> {
+    check_terminal()?;
+    eprint!("{prompt} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn render_resource() {
+        let value = json!({
+            "location": "westus",
+            "properties": {"count": 1, "none": null, "ratio": 0.5, "sub": {}, "zones": ["1"]},
+            "tags": {"a b": "c", "d": {"e": 1}},
+        });
+        let content = render(&value).unwrap();
+        assert_eq!(
+            content,
+            r#"location = "westus"
+properties {
+  count = 1
+  none = null
+  ratio = 0.5
+  sub {}
+  zones = ["1"]
+}
+tags = {
+  "a b" = "c"
+  d = {
+    e = 1
+  }
+}
+"#
+        );
+        let body = hcl::parse(&content).unwrap();
+        let v: Value = hcl::from_body(body).unwrap();
+        assert_eq!(v, value);
+        assert!(render(&json!([1])).is_err());
+
+        // The top level key that isn't a valid identifier can't be rendered, but split out.
+        let value = json!({"@odata.type": "foo", "properties": {"count": 1}});
+        assert!(render(&value).is_err());
+        let (body, others) = split(&value).unwrap();
+        assert_eq!(body, json!({"properties": {"count": 1}}));
+        assert_eq!(Value::Object(others), json!({"@odata.type": "foo"}));
+        assert!(render(&body).is_ok());
+    }
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!(diff("a\nb", "a\nb\n"), None);
+        assert_eq!(
+            diff("a\nb\nc", "a\nx\nc\nd").unwrap(),
+            "  a\n- b\n+ x\n  c\n+ d"
+        );
+    }
+}
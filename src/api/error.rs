@@ -0,0 +1,195 @@
+use std::fmt::Display;
+
+use azure_core::error::ErrorKind;
+use serde::Deserialize;
+
+// The exit codes of the CLI, so that scripts can branch on the failures.
+pub const EXIT_GENERAL: u8 = 1;
+pub const EXIT_USAGE: u8 = 2;
+pub const EXIT_NOT_FOUND: u8 = 3;
+pub const EXIT_AUTH: u8 = 4;
+pub const EXIT_THROTTLED: u8 = 5;
+pub const EXIT_RESPONSE: u8 = 6;
+
+// ArmError is the error defined by the ARM error response envelope:
+// https://github.com/Azure/azure-resource-manager-rpc/blob/master/v1.0/common-api-details.md#error-response-content
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArmError {
+    pub code: String,
+    pub message: String,
+    pub target: Option<String>,
+    #[serde(default)]
+    pub details: Vec<ArmError>,
+    #[serde(default)]
+    pub additional_info: Vec<AdditionalInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct AdditionalInfo {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub info: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    error: ArmError,
+}
+
+impl ArmError {
+    fn write(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
+        let pad = "  ".repeat(indent);
+        write!(f, "{pad}({}) {}", self.code, self.message)?;
+        if let Some(target) = &self.target {
+            write!(f, "\n{pad}Target: {target}")?;
+        }
+        if !self.details.is_empty() {
+            write!(f, "\n{pad}Details:")?;
+            for detail in &self.details {
+                writeln!(f)?;
+                detail.write(f, indent + 1)?;
+            }
+        }
+        if !self.additional_info.is_empty() {
+            write!(f, "\n{pad}Additional info:")?;
+            for info in &self.additional_info {
+                write!(f, "\n{pad}  Type: {}", info.type_)?;
+                if let Some(v) = &info.info {
+                    write!(f, "\n{pad}  Info: {v}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// ResponseError is the error response of an API call, which doesn't match any of the successful
+// responses defined in the command metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseError {
+    pub status: u16,
+    pub error: Option<ArmError>,
+    pub body: String,
+}
+
+impl ResponseError {
+    pub fn new(status: u16, body: &[u8]) -> Self {
+        let error = serde_json::from_slice::<Envelope>(body)
+            .ok()
+            .map(|envelope| envelope.error);
+        Self {
+            status,
+            error,
+            body: String::from_utf8_lossy(body).to_string(),
+        }
+    }
+}
+
+impl Display for ResponseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "error response (HTTP {})", self.status)?;
+        match &self.error {
+            Some(error) => {
+                writeln!(f, ":")?;
+                error.write(f, 1)
+            }
+            None if self.body.is_empty() => Ok(()),
+            None => write!(f, ":\n\n{}", self.body),
+        }
+    }
+}
+
+impl std::error::Error for ResponseError {}
+
+// exit_code returns the exit code of the CLI for the error.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<ResponseError>() {
+            return exit_code_for_status(err.status);
+        }
+        if let Some(err) = cause.downcast_ref::<azure_core::Error>() {
+            match err.kind() {
+                ErrorKind::HttpResponse { status, .. } => {
+                    return exit_code_for_status(u16::from(*status));
+                }
+                ErrorKind::Credential => return EXIT_AUTH,
+                _ => {}
+            }
+        }
+        if let Some(err) = cause.downcast_ref::<clap::Error>() {
+            return err.exit_code() as u8;
+        }
+    }
+    EXIT_GENERAL
+}
+
+fn exit_code_for_status(status: u16) -> u8 {
+    match status {
+        404 => EXIT_NOT_FOUND,
+        401 | 403 => EXIT_AUTH,
+        429 => EXIT_THROTTLED,
+        _ => EXIT_RESPONSE,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn response_error() {
+        let body = r#"{
+  "error": {
+    "code": "InvalidTemplate",
+    "message": "Deployment template validation failed.",
+    "target": "/subscriptions/sub",
+    "details": [
+      {
+        "code": "InvalidParameter",
+        "message": "The parameter 'name' is invalid."
+      }
+    ],
+    "additionalInfo": [
+      {
+        "type": "TemplateViolation",
+        "info": {"lineNumber": 1}
+      }
+    ]
+  }
+}"#;
+        let err = ResponseError::new(400, body.as_bytes());
+        assert_eq!(err.error.as_ref().unwrap().details.len(), 1);
+        assert_eq!(
+            err.to_string(),
+            r#"error response (HTTP 400):
+  (InvalidTemplate) Deployment template validation failed.
+  Target: /subscriptions/sub
+  Details:
+    (InvalidParameter) The parameter 'name' is invalid.
+  Additional info:
+    Type: TemplateViolation
+    Info: {"lineNumber":1}"#
+        );
+
+        let err = ResponseError::new(502, b"Bad Gateway");
+        assert_eq!(err.error, None);
+        assert_eq!(err.to_string(), "error response (HTTP 502):\n\nBad Gateway");
+    }
+
+    #[test]
+    fn exit_codes() {
+        let code = |status| exit_code(&ResponseError::new(status, b"").into());
+        assert_eq!(code(404), EXIT_NOT_FOUND);
+        assert_eq!(code(401), EXIT_AUTH);
+        assert_eq!(code(403), EXIT_AUTH);
+        assert_eq!(code(429), EXIT_THROTTLED);
+        assert_eq!(code(500), EXIT_RESPONSE);
+        assert_eq!(
+            exit_code(&anyhow::Error::from(ResponseError::new(404, b"")).context("invoking")),
+            EXIT_NOT_FOUND
+        );
+        assert_eq!(exit_code(&anyhow::anyhow!("other")), EXIT_GENERAL);
+    }
+}
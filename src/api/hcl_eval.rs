@@ -0,0 +1,146 @@
+// The evaluation context of the HCL payloads, so that one payload file can be reused across
+// environments. It provides:
+//
+// - The variables from "--var-file" and "--var", referenced as "var.<name>"
+// - The functions: env(), file(), base64encode(), jsonencode() and uuid()
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context as _, Result};
+use base64::Engine;
+use hcl::eval::{Context, FuncArgs, FuncDef, ParamType};
+use hcl::{Map, Value};
+
+// context builds the evaluation context. The variables are read from the var files in order, and
+// then from the "KEY=VALUE" vars, where the later ones take precedence.
+pub fn context(vars: &[String], var_files: &[PathBuf]) -> Result<Context<'static>> {
+    let mut values = Map::new();
+    for path in var_files {
+        let content =
+            std::fs::read_to_string(path).context(format!("reading the var file {path:?}"))?;
+        let file_values: Map<String, Value> =
+            hcl::from_str(&content).context(format!("parsing the var file {path:?}"))?;
+        values.extend(file_values);
+    }
+    for var in vars {
+        let (k, v) = var
+            .split_once('=')
+            .ok_or(anyhow!(r#"expect "KEY=VALUE", got {var:?}"#))?;
+        // The value is in JSON, or regarded as a string otherwise.
+        let v = serde_json::from_str::<serde_json::Value>(v).unwrap_or(v.into());
+        values.insert(k.to_string(), hcl::to_value(v)?);
+    }
+
+    let mut ctx = Context::new();
+    ctx.declare_var("var", Value::Object(values));
+    ctx.declare_func(
+        "env",
+        FuncDef::builder().param(ParamType::String).build(env),
+    );
+    ctx.declare_func(
+        "file",
+        FuncDef::builder().param(ParamType::String).build(file),
+    );
+    ctx.declare_func(
+        "base64encode",
+        FuncDef::builder()
+            .param(ParamType::String)
+            .build(base64encode),
+    );
+    ctx.declare_func(
+        "jsonencode",
+        FuncDef::builder().param(ParamType::Any).build(jsonencode),
+    );
+    ctx.declare_func("uuid", FuncDef::builder().build(uuid));
+    Ok(ctx)
+}
+
+// The parameter types are validated before calling the functions.
+
+fn env(args: FuncArgs) -> Result<Value, String> {
+    let name = args[0].as_str().unwrap();
+    std::env::var(name)
+        .map(Value::from)
+        .map_err(|_| format!("environment variable {name:?} is not set"))
+}
+
+fn file(args: FuncArgs) -> Result<Value, String> {
+    let path = args[0].as_str().unwrap();
+    std::fs::read_to_string(path)
+        .map(Value::from)
+        .map_err(|e| format!("reading the file {path:?}: {e}"))
+}
+
+fn base64encode(args: FuncArgs) -> Result<Value, String> {
+    let s = args[0].as_str().unwrap();
+    Ok(base64::engine::general_purpose::STANDARD.encode(s).into())
+}
+
+fn jsonencode(args: FuncArgs) -> Result<Value, String> {
+    serde_json::to_string(&args[0])
+        .map(Value::from)
+        .map_err(|e| e.to_string())
+}
+
+fn uuid(_: FuncArgs) -> Result<Value, String> {
+    Ok(uuid::Uuid::new_v4().to_string().into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn evaluate() {
+        let mut var_file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(
+            &mut var_file,
+            br#"name = "foo"
+count = 1"#,
+        )
+        .unwrap();
+        let ctx = context(
+            &["count=2".to_string(), "env=dev".to_string()],
+            &[var_file.path().to_path_buf()],
+        )
+        .unwrap();
+
+        // Any of the existing environment variables, rather than setting one in the test.
+        let (env_name, env_value) = std::env::vars_os()
+            .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+            .find(|(k, _)| {
+                !k.is_empty() && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+            .expect("no environment variable");
+        let content = format!(
+            r#"
+name = "${{var.name}}-${{var.env}}"
+count = var.count
+from_env = env("{env_name}")
+encoded = base64encode("foo")
+json = jsonencode({{a = 1}})
+"#
+        );
+        let v: serde_json::Value = hcl::eval::from_str(&content, &ctx).unwrap();
+        assert_eq!(
+            v,
+            json!({
+                "name": "foo-dev",
+                "count": 2,
+                "from_env": env_value,
+                "encoded": "Zm9v",
+                "json": r#"{"a":1}"#,
+            })
+        );
+
+        let v: serde_json::Value = hcl::eval::from_str(r#"id = uuid()"#, &ctx).unwrap();
+        assert_eq!(v["id"].as_str().unwrap().len(), 36);
+        assert!(
+            hcl::eval::from_str::<serde_json::Value>(r#"x = env("AZ_RS_TEST_NOT_SET")"#, &ctx)
+                .is_err()
+        );
+        assert!(context(&["a".to_string()], &[]).is_err());
+    }
+}
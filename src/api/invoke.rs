@@ -1,16 +1,28 @@
-use crate::{api::metadata_command::Method, cmd};
+use crate::{
+    api::metadata_command::Method,
+    client::{poller::ProgressFunc, response::Response, RunOptions},
+    cmd,
+};
 
-use super::metadata_command::{Operation, Schema};
-use anyhow::{Context, Result, bail};
+use super::arg_type::ArgType;
+use super::error::ResponseError;
+use super::metadata_command::{Http, Operation, Schema};
+use super::shorthand;
+use anyhow::{Context, Result, anyhow, bail};
 use clap::ArgMatches;
-use core::unreachable;
 use std::collections::HashMap;
+use std::fmt::Display;
+
+// The query parameter of the page size, which is sent by "--page-size".
+const TOP_QUERY_PARAM: &str = "$top";
 
 pub struct OperationInvocation {
     operation: Operation,
     matches: ArgMatches,
     id: Option<String>,
     body: Option<serde_json::Value>,
+    defaults: HashMap<String, String>,
+    progress: Option<ProgressFunc>,
 }
 
 impl OperationInvocation {
@@ -25,10 +37,46 @@ impl OperationInvocation {
             matches: matches.clone(),
             id: id.clone(),
             body: body.clone(),
+            defaults: HashMap::new(),
+            progress: None,
         }
     }
 
+    // with_defaults sets the default values of the path parameters, keyed by the arg var, which
+    // are used when the args are absent.
+    pub fn with_defaults(mut self, defaults: HashMap<String, String>) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    // with_progress sets the function to report the progress of the long running operation.
+    pub fn with_progress(mut self, progress: Option<ProgressFunc>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    // invoke invokes the operation and returns the response body.
+    // For pageable responses, the "nextLink" is followed and all the pages are merged into one "value" array.
     pub async fn invoke(&self, client: &crate::client::Client) -> Result<String> {
+        let mut pages = vec![];
+        self.invoke_paged(client, |page| {
+            pages.push(page);
+            Ok(())
+        })
+        .await?;
+        merge_pages(pages)
+    }
+
+    // invoke_paged invokes the operation and calls the page_func for each page of the response as it arrives.
+    // For non-pageable responses, the page_func is called once with the whole response body.
+    pub async fn invoke_paged<F>(
+        &self,
+        client: &crate::client::Client,
+        mut page_func: F,
+    ) -> Result<()>
+    where
+        F: FnMut(String) -> Result<()>,
+    {
         if self.operation.http.is_none() {
             bail!(
                 r#"HTTP information not found for operation "{}""#,
@@ -54,33 +102,60 @@ impl OperationInvocation {
         } else {
             path = http.path.clone();
             for param in &http.request.path.params {
-                if let Some(value) = self.matches.get_one::<String>(&param.arg) {
-                    path = path.replace(&format!("{{{}}}", param.name), value);
+                let placeholder = format!("{{{}}}", param.name);
+                let value = match self.param_value(&param.arg, &param.type_)? {
+                    Some(value) => Some(value),
+                    None => self.defaults.get(&param.arg).cloned(),
+                };
+                if let Some(value) = value {
+                    path = path.replace(&placeholder, &value);
                 } else if let Some(true) = param.required {
                     bail!("missing required path parameter: {}", param.name);
                 } else {
-                    unreachable!(
-                        r#"optional path parameter "{}" not supported yet!"#,
-                        param.name
-                    )
+                    // The absent optional path parameter is removed together with its segment.
+                    path = path
+                        .replace(&format!("/{placeholder}"), "")
+                        .replace(&placeholder, "");
                 }
             }
         }
         let mut query_pairs = HashMap::new();
         for param in &http.request.query.consts {
-            // Only handle api-version const query so far.
-            if param.name == "api-version" {
-                if let Some(value) = self.matches.get_one::<String>("api-version") {
-                    query_pairs.insert(param.name.clone(), value.clone());
-                } else {
-                    query_pairs.insert(param.name.clone(), param.default.value.clone());
-                }
-            }
+            // The api-version can be overridden by the "--api-version" option.
+            let value = match self.matches.get_one::<String>("api-version") {
+                Some(value) if param.name == "api-version" => value.clone(),
+                _ => param.default.value.clone(),
+            };
+            query_pairs.insert(param.name.clone(), value);
         }
         if let Some(params) = http.request.query.params.as_ref() {
             for param in params {
-                if let Some(value) = self.matches.get_one::<String>(&param.arg) {
-                    query_pairs.insert(param.name.clone(), value.clone());
+                if let Some(value) = self.param_value(&param.arg, &param.type_)? {
+                    query_pairs.insert(param.name.clone(), value);
+                }
+            }
+        }
+        if let Some(page_size) = self.page_size() {
+            // The page size is passed via "$top", which is carried over by the "nextLink" of
+            // the subsequent pages. The APIs that don't declare it may reject or ignore it.
+            if !has_query_param(http, TOP_QUERY_PARAM) {
+                bail!(
+                    r#""--{}" is not supported by this API, which has no "{TOP_QUERY_PARAM}" query parameter"#,
+                    cmd::PAGE_SIZE_OPTION
+                );
+            }
+            query_pairs.insert(TOP_QUERY_PARAM.to_string(), page_size.to_string());
+        }
+        let mut headers = HashMap::new();
+        if let Some(header) = http.request.header.as_ref() {
+            for param in header.consts.iter().flatten() {
+                headers.insert(param.name.clone(), param.default.value.clone());
+            }
+            for param in header.params.iter().flatten() {
+                if let Some(value) = self.param_value(&param.arg, &param.type_)? {
+                    headers.insert(param.name.clone(), value);
+                } else if let Some(true) = param.required {
+                    bail!("missing required header parameter: {}", param.name);
                 }
             }
         }
@@ -96,34 +171,267 @@ impl OperationInvocation {
             }
         } else {
             None
+        };
+        if let (Some(body), Some(schema)) = (&body, request_schema(http)) {
+            if !self.skip_validation() {
+                let errors = schema.validate_body(body, http.request.method == Method::Patch);
+                if !errors.is_empty() {
+                    bail!(
+                        "invalid request body (use \"--{}\" to skip the validation):\n  {}",
+                        cmd::SKIP_VALIDATION_OPTION,
+                        errors.join("\n  ")
+                    );
+                }
+            }
         }
-        .map(|v| bytes::Bytes::from(v.to_string()));
+        let body = body.map(|v| bytes::Bytes::from(v.to_string()));
 
+        if self.no_wait() {
+            let (response, poller) = client
+                .begin(
+                    http.request.method.into(),
+                    path.as_str(),
+                    &query_pairs,
+                    &headers,
+                    body,
+                    Some(self.run_options()),
+                )
+                .await?;
+            // The LRO that completes synchronously outputs its response as is. The initial response
+            // of the in-progress LRO (e.g. 202) is not checked, as the response metadata usually only
+            // lists the status codes of the final response.
+            let Some(poller) = poller else {
+                return page_func(check_response(http, &response)?);
+            };
+            return page_func(serde_json::to_string(&poller.state()?)?);
+        }
         let response = client
             .run(
                 http.request.method.into(),
                 path.as_str(),
-                &query_pairs["api-version"],
+                &query_pairs,
+                &headers,
                 body,
-                None,
+                Some(self.run_options()),
             )
             .await?;
-        for response_meta in &http.responses {
-            if let Some(status_codes) = &response_meta.status_code {
-                if status_codes.contains(&(u16::from(response.status_code) as i64)) {
-                    return Ok(String::from_utf8(response.body.to_vec())?);
-                }
+        let body = check_response(http, &response)?;
+
+        // Only the list operations (i.e. GET) are pageable.
+        if http.request.method != Method::Get {
+            return page_func(body);
+        }
+        let Some(mut page) = Page::from_body(&body) else {
+            return page_func(body);
+        };
+
+        // The "nextLink" carries over the query parameters, except for the api-version in some cases.
+        let next_query: HashMap<_, _> = query_pairs
+            .into_iter()
+            .filter(|(k, _)| k == "api-version")
+            .collect();
+        let mut remaining = self.max_items();
+        loop {
+            if let Some(n) = remaining {
+                page.truncate(n);
+                remaining = Some(n - page.len());
+            }
+            let next_link = page.next_link.take();
+            page_func(page.to_string())?;
+
+            let Some(next_link) = next_link else {
+                return Ok(());
+            };
+            if remaining == Some(0) {
+                return Ok(());
             }
+            let response = client
+                .run(
+                    azure_core::http::Method::Get,
+                    next_link.as_str(),
+                    &next_query,
+                    &headers,
+                    None,
+                    None,
+                )
+                .await?;
+            let body = check_response(http, &response)?;
+            page = Page::from_body(&body).ok_or(anyhow!(
+                r#"the page returned by the "nextLink" {next_link} is not a valid page"#
+            ))?;
         }
-        bail!(
-            "error response: {}\n\n{}",
-            response.status_code,
-            String::from_utf8_lossy(&response.body)
-        );
     }
+
+    fn run_options(&self) -> RunOptions<'static> {
+        RunOptions {
+            poller_options: self.operation.poller_options(),
+            poll_options: Some(cmd::poll_until_done_options(
+                &self.matches,
+                self.progress.clone(),
+            )),
+            ..Default::default()
+        }
+    }
+
+    // param_value returns the value of the path, query or header parameter. The array values are
+    // joined by comma.
+    fn param_value(&self, arg: &str, type_: &str) -> Result<Option<String>> {
+        let Some(value) = self.matches.try_get_one::<String>(arg).ok().flatten() else {
+            return Ok(None);
+        };
+        if ArgType::from_metadata(type_) != ArgType::Array {
+            return Ok(Some(value.clone()));
+        }
+        let serde_json::Value::Array(values) = ArgType::Array.to_json(value)? else {
+            return Ok(None);
+        };
+        let values: Vec<_> = values
+            .into_iter()
+            .map(|v| match v {
+                serde_json::Value::String(s) => s,
+                v => v.to_string(),
+            })
+            .collect();
+        Ok(Some(values.join(",")))
+    }
+
+    fn skip_validation(&self) -> bool {
+        self.matches
+            .try_get_one::<bool>(cmd::SKIP_VALIDATION_OPTION)
+            .ok()
+            .flatten()
+            .cloned()
+            .unwrap_or(false)
+    }
+
+    fn no_wait(&self) -> bool {
+        self.matches
+            .try_get_one::<bool>(cmd::NO_WAIT_OPTION)
+            .ok()
+            .flatten()
+            .cloned()
+            .unwrap_or(false)
+    }
+
+    fn max_items(&self) -> Option<usize> {
+        self.matches
+            .try_get_one::<usize>(cmd::MAX_ITEMS_OPTION)
+            .ok()
+            .flatten()
+            .cloned()
+    }
+
+    fn page_size(&self) -> Option<usize> {
+        self.matches
+            .try_get_one::<usize>(cmd::PAGE_SIZE_OPTION)
+            .ok()
+            .flatten()
+            .cloned()
+    }
+}
+
+// has_query_param tells whether the operation declares the query parameter.
+fn has_query_param(http: &Http, name: &str) -> bool {
+    http.request
+        .query
+        .params
+        .iter()
+        .flatten()
+        .any(|param| param.name == name)
+}
+
+fn request_schema(http: &Http) -> Option<&Schema> {
+    http.request
+        .body
+        .as_ref()
+        .and_then(|body| body.json.schema.as_ref())
+}
+
+// check_response returns the response body if the response status code matches any of the
+// successful response metadata, otherwise, returns the error response.
+fn check_response(http: &Http, response: &Response) -> Result<String> {
+    let status = u16::from(response.status_code);
+    for response_meta in &http.responses {
+        if response_meta.is_error.unwrap_or(false) {
+            continue;
+        }
+        if let Some(status_codes) = &response_meta.status_code {
+            if status_codes.contains(&(status as i64)) {
+                return Ok(String::from_utf8(response.body.to_vec())?);
+            }
+        }
+    }
+    Err(ResponseError::new(status, &response.body).into())
+}
+
+// Page represents one page of a pageable response, which is an object containing a "value" array,
+// and optionally a "nextLink" pointing to the next page.
+struct Page {
+    object: serde_json::Map<String, serde_json::Value>,
+    next_link: Option<String>,
 }
 
-struct BodyBuilder<'a>(&'a ArgMatches);
+impl Page {
+    fn from_body(body: &str) -> Option<Self> {
+        let serde_json::Value::Object(mut object) = serde_json::from_str(body).ok()? else {
+            return None;
+        };
+        if !object.get("value").is_some_and(|v| v.is_array()) {
+            return None;
+        }
+        let next_link = object
+            .remove("nextLink")
+            .and_then(|v| v.as_str().map(String::from));
+        Some(Self { object, next_link })
+    }
+
+    fn values_mut(&mut self) -> &mut Vec<serde_json::Value> {
+        self.object
+            .get_mut("value")
+            .and_then(|v| v.as_array_mut())
+            .expect(r#"page contains the "value" array"#)
+    }
+
+    fn len(&self) -> usize {
+        self.object
+            .get("value")
+            .and_then(|v| v.as_array())
+            .map_or(0, |v| v.len())
+    }
+
+    fn truncate(&mut self, n: usize) {
+        self.values_mut().truncate(n);
+    }
+}
+
+impl Display for Page {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_json::Value::Object(self.object.clone()))
+    }
+}
+
+// merge_pages merges the pages into the first one, by concatenating their "value" arrays, while the
+// other properties of the first page are kept and the "nextLink" is removed, regardless of the page
+// count. The response of a non-pageable operation is returned as is.
+fn merge_pages(pages: Vec<String>) -> Result<String> {
+    let mut pages = pages.into_iter();
+    let Some(first) = pages.next() else {
+        bail!("no page is returned");
+    };
+    let Some(mut merged) = Page::from_body(&first) else {
+        if pages.as_slice().is_empty() {
+            return Ok(first);
+        }
+        bail!("invalid page: {first}");
+    };
+    for page in pages {
+        let mut page = Page::from_body(&page).ok_or(anyhow!("invalid page: {page}"))?;
+        merged.values_mut().append(page.values_mut());
+    }
+    Ok(merged.to_string())
+}
+
+pub struct BodyBuilder<'a>(pub &'a ArgMatches);
 
 impl<'a> BodyBuilder<'a> {
     pub fn build_body(&self, schema: &Schema) -> Result<serde_json::Value> {
@@ -159,38 +467,51 @@ impl<'a> BodyBuilder<'a> {
 
         match schema.type_.as_str() {
             "object" => {
-                if let Some(arg) = &schema.arg {
-                    if let Some(value) = self.0.get_one::<String>(arg) {
-                        Ok(Some(serde_json::from_str(value)?))
-                    } else {
-                        Ok(None)
-                    }
-                } else if let Some(props) = &schema.props {
-                    let mut map = serde_json::Map::new();
-                    for prop in props {
-                        if let Some(prop_name) = &prop.name {
-                            let value = self
-                                .build_value(prop)
-                                .context(format!("build value for {}", prop_name))?;
-                            if let Some(value) = value {
-                                map.insert(prop_name.clone(), value);
-                            }
-                        } else {
-                            bail!(r#"property {prop:#?} lacks the "name" in the schema"#,);
+                if schema.arg.is_none() && schema.props.is_none() {
+                    bail!(r#"schema {schema:#?} lacks both the "arg" and "props""#);
+                }
+                let value = match &schema.arg {
+                    Some(arg) => self.arg_value(arg, schema)?,
+                    None => None,
+                };
+                let Some(props) = &schema.props else {
+                    return Ok(value);
+                };
+
+                // The values of the nested args are merged into the value of the object arg (if any).
+                let mut map = serde_json::Map::new();
+                for prop in props {
+                    // Only the nested args scoped by the object arg (e.g. "obj.str" of "obj") are
+                    // merged, the other props are set by the object arg alone.
+                    if let (Some(parent), Some(arg)) = (&schema.arg, &prop.arg) {
+                        if !arg.starts_with(&format!("{parent}.")) {
+                            continue;
                         }
                     }
-                    if map.is_empty() {
-                        Ok(None)
+                    if let Some(prop_name) = &prop.name {
+                        let value = self
+                            .build_value(prop)
+                            .context(format!("build value for {}", prop_name))?;
+                        if let Some(value) = value {
+                            map.insert(prop_name.clone(), value);
+                        }
                     } else {
-                        Ok(Some(serde_json::Value::Object(map)))
+                        bail!(r#"property {prop:#?} lacks the "name" in the schema"#,);
                     }
-                } else {
-                    bail!(r#"schema {schema:#?} lacks both the "arg" and "props""#);
+                }
+                match value {
+                    Some(serde_json::Value::Object(mut base)) => {
+                        base.extend(map);
+                        Ok(Some(serde_json::Value::Object(base)))
+                    }
+                    Some(value) if map.is_empty() => Ok(Some(value)),
+                    _ if map.is_empty() => Ok(None),
+                    _ => Ok(Some(serde_json::Value::Object(map))),
                 }
             }
             "string" => {
                 if let Some(arg) = &schema.arg {
-                    if let Some(value) = self.0.get_one::<String>(arg) {
+                    if let Some(value) = self.0.try_get_one::<String>(arg).ok().flatten() {
                         Ok(Some((value.clone()).into()))
                     } else {
                         Ok(None)
@@ -200,35 +521,123 @@ impl<'a> BodyBuilder<'a> {
                 }
             }
             _ => {
-                // The other types are all passed in its json form, hence can be directly decoded.
+                // The other types are converted to JSON by their types.
                 if let Some(arg) = &schema.arg {
-                    if let Some(value) = self.0.get_one::<String>(arg) {
-                        // Since there exists string-like types (e.g. ResourceLocation) in the
-                        // metadata file. We have to firstly try to parse it as a non-string input.
-                        // If it failed, parse it as a JSON string.
-                        // TODO: Update the metadata file to eliminate bizzard string-like types like
-                        // "ResourceLocation", etc. Just use the primary types.
-                        if let Ok(v) = serde_json::from_str(value) {
-                            Ok(v)
-                        } else {
-                            Ok(Some(serde_json::Value::String(value.clone())))
-                        }
-                    } else {
-                        Ok(None)
-                    }
+                    self.arg_value(arg, schema)
                 } else {
                     bail!(r#"schema "{schema:#?}" lacks the "arg" in the schema"#);
                 }
             }
         }
     }
+
+    // arg_value converts the argument value to JSON by its type, where the object and array values
+    // can be in the shorthand syntax.
+    fn arg_value(&self, arg: &str, schema: &Schema) -> Result<Option<serde_json::Value>> {
+        // The arg might not be defined, e.g. the nested args of a hidden arg.
+        let Some(value) = self.0.try_get_one::<String>(arg).ok().flatten() else {
+            return Ok(None);
+        };
+        let v = ArgType::from_metadata(&schema.type_).to_json(value)?;
+        Ok(Some(shorthand::coerce(v, schema)?))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::azidentityext::access_token_credential::AccessTokenCredential;
+    use crate::client::{Client, NewClientOptions};
+    use azure_core::http::policies::{Policy, PolicyResult};
+    use azure_core::http::{
+        ClientOptions, Context, RawResponse, Request, StatusCode, headers::Headers,
+    };
     use clap::{Arg, Command};
     use serde_json::Value;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    // CannedPolicy replies the requests with the canned responses in order, instead of sending
+    // them, and records the requested URLs.
+    #[derive(Debug)]
+    struct CannedPolicy {
+        responses: Mutex<VecDeque<(StatusCode, Vec<(&'static str, &'static str)>, &'static str)>>,
+        urls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for CannedPolicy {
+        async fn send(
+            &self,
+            _: &Context,
+            request: &mut Request,
+            _: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            let url = request.url().to_string();
+            let (status, headers, body) = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_else(|| panic!("unexpected request to {url}"));
+            self.urls.lock().unwrap().push(url);
+            let mut resp_headers = Headers::new();
+            for (k, v) in headers {
+                resp_headers.insert(k, v);
+            }
+            Ok(RawResponse::from_bytes(status, resp_headers, body))
+        }
+    }
+
+    // canned_client returns the client whose requests are replied by the canned responses.
+    fn canned_client(
+        responses: Vec<(StatusCode, Vec<(&'static str, &'static str)>, &'static str)>,
+    ) -> (Client, Arc<CannedPolicy>) {
+        let policy = Arc::new(CannedPolicy {
+            responses: Mutex::new(responses.into()),
+            urls: Mutex::new(vec![]),
+        });
+        let client = Client::new(
+            "https://management.azure.com",
+            ["https://management.azure.com/.default"],
+            AccessTokenCredential::new("token".to_string()).unwrap(),
+            Some(NewClientOptions {
+                client_options: ClientOptions {
+                    per_try_policies: vec![policy.clone()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        (client, policy)
+    }
+
+    // operation returns the operation of the method and path, whose successful response has one
+    // of the status codes.
+    fn operation(method: &str, path: &str, status_codes: &[i64]) -> Operation {
+        serde_json::from_value(serde_json::json!({
+            "http": {
+                "path": path,
+                "request": {
+                    "method": method,
+                    "path": {"params": []},
+                    "query": {
+                        "consts": [{
+                            "readOnly": true,
+                            "const": true,
+                            "default": {"value": "2024-01-01"},
+                            "type": "string",
+                            "name": "api-version",
+                            "required": true
+                        }]
+                    }
+                },
+                "responses": [{"statusCode": status_codes}]
+            }
+        }))
+        .unwrap()
+    }
 
     #[test]
     fn build_value() {
@@ -349,4 +758,277 @@ mod test {
         .unwrap();
         assert_eq!(value, expect);
     }
+
+    #[test]
+    fn build_value_nested() {
+        let cmd = Command::new("test")
+            .arg(Arg::new("obj").long("obj"))
+            .arg(Arg::new("obj.int").long("obj.int"))
+            .arg(Arg::new("obj.str").long("obj.str"));
+        let schema = Schema {
+            type_: "object".to_string(),
+            props: Some(vec![Schema {
+                type_: "object".to_string(),
+                arg: Some("obj".to_string()),
+                name: Some("obj".to_string()),
+                props: Some(vec![
+                    Schema {
+                        type_: "integer32".to_string(),
+                        arg: Some("obj.int".to_string()),
+                        name: Some("int".to_string()),
+                        ..Schema::default()
+                    },
+                    Schema {
+                        type_: "string".to_string(),
+                        arg: Some("obj.str".to_string()),
+                        name: Some("str".to_string()),
+                        ..Schema::default()
+                    },
+                ]),
+                ..Schema::default()
+            }]),
+            ..Schema::default()
+        };
+
+        let m = cmd
+            .clone()
+            .get_matches_from(vec!["test", "--obj.int", "1", "--obj.str", "a"]);
+        assert_eq!(
+            BodyBuilder(&m).build_body(&schema).unwrap(),
+            serde_json::json!({"obj": {"int": 1, "str": "a"}})
+        );
+
+        // The nested args override the value of the object arg.
+        let m =
+            cmd.clone()
+                .get_matches_from(vec!["test", "--obj", "int=1 str=a", "--obj.str", "b"]);
+        assert_eq!(
+            BodyBuilder(&m).build_body(&schema).unwrap(),
+            serde_json::json!({"obj": {"int": 1, "str": "b"}})
+        );
+
+        let m = cmd.get_matches_from(vec!["test"]);
+        assert_eq!(
+            BodyBuilder(&m).build_body(&schema).unwrap(),
+            serde_json::json!({})
+        );
+    }
+
+    #[test]
+    fn page_from_body() {
+        assert!(Page::from_body("").is_none());
+        assert!(Page::from_body(r#"{"name": "foo"}"#).is_none());
+        assert!(Page::from_body(r#"{"value": "foo"}"#).is_none());
+
+        let page = Page::from_body(r#"{"value": [1, 2], "nextLink": "https://foo"}"#).unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(page.next_link, Some("https://foo".to_string()));
+        assert_eq!(page.to_string(), r#"{"value":[1,2]}"#);
+
+        let page = Page::from_body(r#"{"value": [], "nextLink": null}"#).unwrap();
+        assert_eq!(page.len(), 0);
+        assert_eq!(page.next_link, None);
+    }
+
+    #[test]
+    fn merge_pages() {
+        assert_eq!(
+            super::merge_pages(vec!["".to_string()]).unwrap(),
+            "".to_string()
+        );
+        assert_eq!(
+            super::merge_pages(vec![r#"{"value":[1]}"#.to_string()]).unwrap(),
+            r#"{"value":[1]}"#.to_string()
+        );
+        assert_eq!(
+            super::merge_pages(vec![
+                r#"{"value":[1,2]}"#.to_string(),
+                r#"{"value":[]}"#.to_string(),
+                r#"{"value":[3]}"#.to_string(),
+            ])
+            .unwrap(),
+            r#"{"value":[1,2,3]}"#.to_string()
+        );
+        assert!(super::merge_pages(vec!["{}".to_string(), "{}".to_string()]).is_err());
+
+        // The other properties of the first page are kept, and the "nextLink" is removed.
+        let merged = |pages: Vec<&str>| -> Value {
+            serde_json::from_str(
+                &super::merge_pages(pages.into_iter().map(String::from).collect()).unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            merged(vec![
+                r#"{"value": [1], "count": 3, "nextLink": "https://foo"}"#
+            ]),
+            serde_json::json!({"value": [1], "count": 3})
+        );
+        assert_eq!(
+            merged(vec![
+                r#"{"value": [1], "count": 3, "nextLink": "https://foo"}"#,
+                r#"{"value": [2, 3], "count": 3}"#,
+            ]),
+            serde_json::json!({"value": [1, 2, 3], "count": 3})
+        );
+    }
+
+    #[test]
+    fn query_param() {
+        let mut http: Http = serde_json::from_value(serde_json::json!({
+            "path": "/subscriptions/{subscriptionId}/providers/Microsoft.Network/virtualNetworks",
+            "request": {
+                "method": "get",
+                "path": {"params": []},
+                "query": {
+                    "consts": [{
+                        "readOnly": true,
+                        "const": true,
+                        "default": {"value": "2024-11-01"},
+                        "type": "string",
+                        "name": "api-version",
+                        "required": true
+                    }]
+                }
+            },
+            "responses": []
+        }))
+        .unwrap();
+        assert!(!has_query_param(&http, TOP_QUERY_PARAM));
+        assert!(!has_query_param(&http, "api-version"));
+
+        http.request.query.params = Some(
+            serde_json::from_value(serde_json::json!([{
+                "arg": "$top",
+                "description": "The maximum number of items per page",
+                "name": "$top",
+                "type": "integer32"
+            }]))
+            .unwrap(),
+        );
+        assert!(has_query_param(&http, TOP_QUERY_PARAM));
+    }
+
+    #[tokio::test]
+    async fn no_wait() {
+        let operation = operation(
+            "put",
+            "/subscriptions/sub/providers/Microsoft.Foo/foos/foo",
+            &[200, 201],
+        );
+        let matches = Command::new("test")
+            .arg(
+                Arg::new(cmd::NO_WAIT_OPTION)
+                    .long(cmd::NO_WAIT_OPTION)
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .get_matches_from(vec!["test", "--no-wait"]);
+        let (client, policy) = canned_client(vec![(
+            StatusCode::Accepted,
+            vec![(
+                "azure-asyncoperation",
+                "https://management.azure.com/operations/op",
+            )],
+            "",
+        )]);
+        let body = OperationInvocation::new(&operation, &matches, &None, &None)
+            .invoke(&client)
+            .await
+            .unwrap();
+        let state: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(state["status"], "InProgress");
+        assert!(state["resumeToken"].is_string());
+        assert_eq!(policy.urls.lock().unwrap().len(), 1);
+    }
+
+    const LIST_URL: &str = "https://management.azure.com/subscriptions/sub/providers/Microsoft.Foo/foos?api-version=2024-01-01";
+
+    // pages returns the canned responses of three pages, linked by their "nextLink".
+    fn pages() -> Vec<(StatusCode, Vec<(&'static str, &'static str)>, &'static str)> {
+        vec![
+            (
+                StatusCode::Ok,
+                vec![],
+                r#"{"value": [1, 2], "nextLink": "https://management.azure.com/subscriptions/sub/providers/Microsoft.Foo/foos?api-version=2024-01-01&$skiptoken=2"}"#,
+            ),
+            (
+                StatusCode::Ok,
+                vec![],
+                r#"{"value": [3, 4], "nextLink": "https://management.azure.com/subscriptions/sub/providers/Microsoft.Foo/foos?api-version=2024-01-01&$skiptoken=4"}"#,
+            ),
+            (StatusCode::Ok, vec![], r#"{"value": [5]}"#),
+        ]
+    }
+
+    #[tokio::test]
+    async fn next_link() {
+        let operation = operation(
+            "get",
+            "/subscriptions/sub/providers/Microsoft.Foo/foos",
+            &[200],
+        );
+        let cmd = Command::new("test").arg(
+            Arg::new(cmd::MAX_ITEMS_OPTION)
+                .long(cmd::MAX_ITEMS_OPTION)
+                .value_parser(clap::value_parser!(usize)),
+        );
+
+        // All the pages are merged into one.
+        let matches = cmd.clone().get_matches_from(vec!["test"]);
+        let (client, policy) = canned_client(pages());
+        let body = OperationInvocation::new(&operation, &matches, &None, &None)
+            .invoke(&client)
+            .await
+            .unwrap();
+        assert_eq!(body, r#"{"value":[1,2,3,4,5]}"#);
+        assert_eq!(
+            *policy.urls.lock().unwrap(),
+            vec![
+                LIST_URL.to_string(),
+                format!("{LIST_URL}&$skiptoken=2"),
+                format!("{LIST_URL}&$skiptoken=4"),
+            ]
+        );
+
+        // The pages are passed one by one as they arrive, i.e. "--stream-pages".
+        let (client, _) = canned_client(pages());
+        let mut streamed = vec![];
+        OperationInvocation::new(&operation, &matches, &None, &None)
+            .invoke_paged(&client, |page| {
+                streamed.push(page);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            streamed,
+            vec![
+                r#"{"value":[1,2]}"#,
+                r#"{"value":[3,4]}"#,
+                r#"{"value":[5]}"#
+            ]
+        );
+
+        // The "nextLink" is no longer followed once "--max-items" is reached, in the middle of the
+        // second page.
+        let matches = cmd.get_matches_from(vec!["test", "--max-items", "3"]);
+        let (client, policy) = canned_client(pages());
+        let body = OperationInvocation::new(&operation, &matches, &None, &None)
+            .invoke(&client)
+            .await
+            .unwrap();
+        assert_eq!(body, r#"{"value":[1,2,3]}"#);
+        assert_eq!(policy.urls.lock().unwrap().len(), 2);
+
+        let (client, _) = canned_client(pages());
+        let mut streamed = vec![];
+        OperationInvocation::new(&operation, &matches, &None, &None)
+            .invoke_paged(&client, |page| {
+                streamed.push(page);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(streamed, vec![r#"{"value":[1,2]}"#, r#"{"value":[3]}"#]);
+    }
 }
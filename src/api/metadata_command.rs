@@ -1,10 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use anyhow::Result;
+use clap::ArgMatches;
 use serde::{Deserialize, Serialize};
 use tower_lsp::lsp_types::CompletionItemKind;
 
+use crate::client::poller::{FinalStateVia, NewPollerOptions};
 use crate::cmd;
+use crate::config::Config;
 
 #[derive(Debug, Clone, Deserialize, Default, Serialize)]
 pub struct Command {
@@ -90,6 +93,49 @@ pub struct Arg {
     #[serde(rename = "additionalProps")]
     pub additional_props: Option<AdditionalPropSchema>,
     pub hide: Option<bool>,
+    // The nested args of an object arg.
+    pub args: Option<Vec<Arg>>,
+}
+
+impl ArgGroup {
+    // all_args returns the args of this group, including the nested args of the object args, whose
+    // options are prefixed by the parent's option, e.g. "security-profile.encryption-at-host".
+    pub fn all_args(&self) -> Vec<Arg> {
+        let mut out = vec![];
+        for arg in &self.args {
+            arg.flatten_into(None, &mut out);
+        }
+        out
+    }
+}
+
+impl Arg {
+    fn flatten_into(&self, prefix: Option<&str>, out: &mut Vec<Arg>) {
+        let mut arg = self.clone();
+        arg.args = None;
+        if let Some(prefix) = prefix {
+            // Only the long options are kept for the nested args.
+            arg.options = self
+                .options
+                .iter()
+                .filter(|opt| opt.len() > 1)
+                .map(|opt| format!("{prefix}.{opt}"))
+                .collect();
+        }
+        let long = arg.options.iter().find(|opt| opt.len() > 1).cloned();
+        out.push(arg);
+
+        if self.hide.unwrap_or(false) {
+            return;
+        }
+        let (Some(args), Some(long)) = (&self.args, long) else {
+            return;
+        };
+        // The nested args of array items can't be set individually.
+        for arg in args.iter().filter(|arg| !arg.var.contains("[]")) {
+            arg.flatten_into(Some(&long), out);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -103,6 +149,17 @@ pub struct Operation {
     pub operation_id: Option<String>,
     pub http: Option<Http>,
     pub when: Option<Vec<String>>,
+    #[serde(rename = "longRunning")]
+    pub long_running: Option<LongRunning>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LongRunning {
+    #[serde(rename = "finalStateVia")]
+    pub final_state_via: Option<FinalStateVia>,
+    // The JSON path to the result's payload in the terminal success response of the Operation-Location LRO.
+    #[serde(rename = "resultPath")]
+    pub result_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -117,6 +174,7 @@ pub struct Request {
     pub method: Method,
     pub path: RequestPath,
     pub query: RequestQuery,
+    pub header: Option<RequestHeader>,
     pub body: Option<Body>,
 }
 
@@ -201,6 +259,22 @@ pub struct RequestQueryParam {
     pub type_: String,
 }
 
+// The header consts are of the same form as the query consts.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RequestHeader {
+    pub consts: Option<Vec<RequestQueryConst>>,
+    pub params: Option<Vec<RequestHeaderParam>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RequestHeaderParam {
+    pub arg: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub required: Option<bool>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DefaultValue {
     pub value: String,
@@ -254,6 +328,49 @@ pub struct Schema {
     pub client_flatten: Option<bool>,
     #[serde(rename = "additionalProps")]
     pub additional_props: Option<AdditionalPropSchema>,
+    pub secret: Option<bool>,
+    pub discriminators: Option<Vec<DiscriminatorSchema>>,
+}
+
+// DiscriminatorSchema is a variant of the polymorphic object, whose extra props (and nested
+// variants) apply when the discriminator property has the value.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiscriminatorSchema {
+    pub property: String,
+    pub value: String,
+    pub props: Option<Vec<Schema>>,
+    pub discriminators: Option<Vec<DiscriminatorSchema>>,
+}
+
+impl DiscriminatorSchema {
+    // collect_secret_props collects the paths of the secret props of the variant, which are at the
+    // same level as the object's own props.
+    fn collect_secret_props(&self, path: &str, out: &mut BTreeSet<String>) {
+        Schema::collect_secret_props_of(self.props.iter().flatten(), path, out);
+        for discriminator in self.discriminators.iter().flatten() {
+            discriminator.collect_secret_props(path, out);
+        }
+    }
+
+    // matched_props appends the props of the variants matched by the object, and returns false if
+    // any of the declared discriminators is not matched, i.e. the object may have unknown props.
+    fn matched_props<'a>(
+        discriminators: &'a [DiscriminatorSchema],
+        map: &serde_json::Map<String, serde_json::Value>,
+        out: &mut Vec<&'a Schema>,
+    ) -> bool {
+        let Some(d) = discriminators
+            .iter()
+            .find(|d| map.get(&d.property).and_then(|v| v.as_str()) == Some(d.value.as_str()))
+        else {
+            return false;
+        };
+        out.extend(d.props.iter().flatten());
+        match &d.discriminators {
+            Some(nested) if !nested.is_empty() => Self::matched_props(nested, map, out),
+            _ => true,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -268,6 +385,45 @@ pub struct AdditionalPropItemSchema {
 }
 
 impl Schema {
+    // is_secret returns true if the value of this property is sensitive, e.g. a password or a key.
+    pub fn is_secret(&self) -> bool {
+        self.secret.unwrap_or(false) || self.type_ == "password"
+    }
+
+    // collect_secret_props collects the paths of the secret properties under the schema, which is
+    // at the path. The path of a property is its dot joined names from the body root, e.g.
+    // "properties.osProfile.adminPassword", where the array items share the path of the array.
+    pub fn collect_secret_props(&self, path: &str, out: &mut BTreeSet<String>) {
+        Self::collect_secret_props_of(self.props.iter().flatten(), path, out);
+        for discriminator in self.discriminators.iter().flatten() {
+            discriminator.collect_secret_props(path, out);
+        }
+        if let Some(item) = &self.item {
+            item.collect_secret_props(path, out);
+        }
+    }
+
+    fn collect_secret_props_of<'a>(
+        props: impl IntoIterator<Item = &'a Schema>,
+        path: &str,
+        out: &mut BTreeSet<String>,
+    ) {
+        for prop in props {
+            let Some(name) = prop.name.as_ref() else {
+                continue;
+            };
+            let prop_path = if path.is_empty() {
+                name.clone()
+            } else {
+                format!("{path}.{name}")
+            };
+            if prop.is_secret() {
+                out.insert(prop_path.clone());
+            }
+            prop.collect_secret_props(&prop_path, out);
+        }
+    }
+
     // shake_body removes all the readOnly attributes from the body.
     pub fn shake_body(&self, body: &mut serde_json::Value) -> Result<()> {
         self.shake_value(body)
@@ -321,6 +477,129 @@ impl Schema {
         }
     }
 
+    // validate_body validates the request body against the schema, and returns the problems found,
+    // each prefixed with the JSON path of the offending value. The body of a partial update (i.e.
+    // PATCH) can omit the required properties, except for those of the array items, as the arrays
+    // are replaced as a whole.
+    pub fn validate_body(&self, body: &serde_json::Value, partial: bool) -> Vec<String> {
+        let mut errors = vec![];
+        self.validate_value(body, "$", partial, &mut errors);
+        errors
+    }
+
+    fn validate_value(
+        &self,
+        value: &serde_json::Value,
+        path: &str,
+        partial: bool,
+        errors: &mut Vec<String>,
+    ) {
+        if value.is_null() {
+            return;
+        }
+        let Some(expect) = self.json_type() else {
+            // Unknown type, skip the validation.
+            return;
+        };
+        let actual = match value {
+            serde_json::Value::Bool(_) => "boolean",
+            serde_json::Value::Number(n) if n.is_f64() => "number",
+            serde_json::Value::Number(_) => "integer",
+            serde_json::Value::String(_) => "string",
+            serde_json::Value::Array(_) => "array",
+            serde_json::Value::Object(_) => "object",
+            serde_json::Value::Null => unreachable!(),
+        };
+        if expect != actual && !(expect == "number" && actual == "integer") {
+            errors.push(format!("{path}: expect {expect}, got {actual}"));
+            return;
+        }
+        match value {
+            serde_json::Value::Array(values) => {
+                if let Some(item) = &self.item {
+                    for (i, value) in values.iter().enumerate() {
+                        item.validate_value(value, &format!("{path}[{i}]"), false, errors);
+                    }
+                }
+            }
+            serde_json::Value::Object(map) => {
+                if self.props.is_none() && self.discriminators.is_none() {
+                    return;
+                }
+                let mut props: Vec<&Schema> = self.props.iter().flatten().collect();
+                let mut all_matched = true;
+                if let Some(discriminators) = self.discriminators.as_ref().filter(|d| !d.is_empty())
+                {
+                    all_matched =
+                        DiscriminatorSchema::matched_props(discriminators, map, &mut props);
+                }
+                for prop in &props {
+                    let Some(name) = &prop.name else {
+                        continue;
+                    };
+                    if !partial
+                        && prop.required.unwrap_or(false)
+                        && !prop.read_only.unwrap_or(false)
+                        && !map.contains_key(name)
+                    {
+                        errors.push(format!("{path}.{name}: required property is missing"));
+                    }
+                }
+                for (k, v) in map {
+                    let prop_path = format!("{path}.{k}");
+                    match props.iter().find(|prop| prop.name.as_ref() == Some(k)) {
+                        Some(prop) if prop.read_only.unwrap_or(false) => {
+                            errors.push(format!("{prop_path}: read-only property can't be set"));
+                        }
+                        Some(prop) => prop.validate_value(v, &prop_path, partial, errors),
+                        // The props of a variant missing from the schema are not reported.
+                        None if self.additional_props.is_none() && all_matched => {
+                            errors.push(format!("{prop_path}: unknown property"));
+                        }
+                        None => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // json_type returns the JSON type of the schema type, or None for unknown types.
+    fn json_type(&self) -> Option<&'static str> {
+        let type_ = self.type_.as_str();
+        if type_.starts_with("integer") {
+            Some("integer")
+        } else if type_.starts_with("float") || type_ == "number" {
+            Some("number")
+        } else if type_ == "boolean" || type_ == "bool" {
+            Some("boolean")
+        } else if type_.starts_with("array") {
+            Some("array")
+        } else if type_ == "object" || type_.starts_with("dict") || type_.starts_with('@') {
+            Some("object")
+        } else if [
+            "string",
+            "ResourceId",
+            "ResourceLocation",
+            "ResourceGroupName",
+            "SubscriptionId",
+            "date",
+            "dateTime",
+            "time",
+            "duration",
+            "uuid",
+            "password",
+            "byte",
+            "binary",
+        ]
+        .contains(&type_)
+        {
+            Some("string")
+        } else {
+            None
+        }
+    }
+
     pub fn to_hover_content(&self) -> String {
         let mut content = format!(
             "{} *{}*, {}",
@@ -406,6 +685,73 @@ impl Operation {
         }
         return false;
     }
+
+    pub fn is_patch(&self) -> bool {
+        self.http
+            .as_ref()
+            .is_some_and(|http| http.request.method == Method::Patch)
+    }
+
+    // may_be_lro returns true if this operation can be a long running operation.
+    pub fn may_be_lro(&self) -> bool {
+        self.http.as_ref().is_some_and(|http| {
+            [Method::Put, Method::Post, Method::Delete, Method::Patch]
+                .contains(&http.request.method)
+        })
+    }
+
+    // poller_options returns the options to build the poller in case this is a LRO.
+    pub fn poller_options(&self) -> Option<NewPollerOptions> {
+        let long_running = self.long_running.as_ref()?;
+        Some(NewPollerOptions {
+            final_state: long_running.final_state_via,
+            operation_location_result_path: long_running.result_path.clone(),
+        })
+    }
+
+    // response_schema returns the schema of the successful response body, if any.
+    pub fn response_schema(&self) -> Option<&Schema> {
+        self.http
+            .as_ref()?
+            .responses
+            .iter()
+            .filter(|resp| !resp.is_error.unwrap_or(false))
+            .find_map(|resp| resp.body.as_ref()?.json.schema.as_ref())
+    }
+
+    // secret_props returns the paths of the secret properties of the request and response body,
+    // whose values are redacted from the debug log.
+    pub fn secret_props(&self) -> BTreeSet<String> {
+        let mut out = BTreeSet::new();
+        for schema in [self.schema_by_path(&[]), self.response_schema()]
+            .into_iter()
+            .flatten()
+        {
+            schema.collect_secret_props("", &mut out);
+        }
+        out
+    }
+
+    // is_pageable returns true if this is a GET operation whose response contains a "nextLink".
+    pub fn is_pageable(&self) -> bool {
+        let Some(http) = self.http.as_ref() else {
+            return false;
+        };
+        if http.request.method != Method::Get {
+            return false;
+        }
+        http.responses
+            .iter()
+            .filter(|resp| !resp.is_error.unwrap_or(false))
+            .filter_map(|resp| resp.body.as_ref())
+            .filter_map(|body| body.json.schema.as_ref())
+            .filter_map(|schema| schema.props.as_ref())
+            .any(|props| {
+                props
+                    .iter()
+                    .any(|prop| prop.name.as_deref() == Some("nextLink"))
+            })
+    }
 }
 
 #[derive(Debug)]
@@ -427,6 +773,46 @@ impl ConditionOpt {
 }
 
 impl Command {
+    // name_args returns the values of the id related args, which are used to select the operation.
+    // The configured defaults only stand in for the required args, so that e.g. a default resource
+    // group doesn't turn a subscription level list into a resource group level one.
+    pub fn name_args(
+        &self,
+        matches: &ArgMatches,
+        defaults: &HashMap<String, String>,
+    ) -> Option<HashMap<String, Option<String>>> {
+        self.arg_groups.iter().find(|ag| ag.name == "").map(|ag| {
+            ag.args
+                .iter()
+                .filter(|arg| !arg.hide.unwrap_or(false))
+                .filter(|arg| arg.id_part.is_some())
+                .map(|arg| {
+                    let value = matches.get_one::<String>(&arg.var).cloned().or_else(|| {
+                        arg.required
+                            .unwrap_or(false)
+                            .then(|| defaults.get(&arg.var).cloned())
+                            .flatten()
+                    });
+                    (arg.var.clone(), value)
+                })
+                .collect()
+        })
+    }
+
+    // id_defaults returns the configured default values of the id related args, keyed by the arg
+    // var.
+    pub fn id_defaults(&self, config: &Config) -> HashMap<String, String> {
+        self.arg_groups
+            .iter()
+            .filter(|ag| ag.name == "")
+            .flat_map(|ag| ag.args.iter())
+            .filter_map(|arg| {
+                let default = config.default_for_id_part(arg.id_part.as_ref()?)?;
+                Some((arg.var.clone(), default.clone()))
+            })
+            .collect()
+    }
+
     pub fn select_operation_by_cond(&self, cond: Option<&String>) -> Option<&Operation> {
         if let Some(cond) = cond {
             self.operations
@@ -509,6 +895,47 @@ impl Command {
         }
     }
 
+    // select_update_operations returns the GET and PUT operations (under the condition, if any) in
+    // case the command supports the generic update, i.e. GET-modify-PUT.
+    pub fn select_update_operations(
+        &self,
+        cond: Option<&String>,
+    ) -> Option<(&Operation, &Operation)> {
+        Some((
+            self.select_operation_by_method(cond, Method::Get)?,
+            self.select_operation_by_method(cond, Method::Put)?,
+        ))
+    }
+
+    // select_operation_by_method selects the operation of the HTTP method, among the ones of the
+    // condition (if any).
+    pub fn select_operation_by_method(
+        &self,
+        cond: Option<&String>,
+        method: Method,
+    ) -> Option<&Operation> {
+        self.operations
+            .iter()
+            .filter(|op| cond.is_none_or(|cond| op.when.iter().flatten().any(|w| w == cond)))
+            .find(|op| {
+                op.http
+                    .as_ref()
+                    .is_some_and(|http| http.request.method == method)
+            })
+    }
+
+    pub fn is_updatable(&self) -> bool {
+        self.select_update_operations(None).is_some()
+    }
+
+    pub fn is_pageable(&self) -> bool {
+        self.operations.iter().any(|op| op.is_pageable())
+    }
+
+    pub fn may_be_lro(&self) -> bool {
+        self.operations.iter().any(|op| op.may_be_lro())
+    }
+
     pub fn contains_request_body(&self) -> bool {
         self.operations
             .first()
@@ -849,4 +1276,290 @@ mod test {
         assert_eq!(input_json, output_json);
         Ok(())
     }
+
+    #[test]
+    fn poller_options() -> Result<(), Box<dyn Error>> {
+        let input = r#"
+{
+  "operationId": "VirtualMachines_Start",
+  "longRunning": {
+    "finalStateVia": "location"
+  }
+}
+"#;
+        let operation: Operation = serde_json::from_str(input)?;
+        let options = operation.poller_options().unwrap();
+        assert_eq!(options.final_state, Some(FinalStateVia::Location));
+        assert_eq!(options.operation_location_result_path, None);
+
+        let operation: Operation =
+            serde_json::from_str(r#"{"operationId": "VirtualMachines_Get"}"#)?;
+        assert!(operation.poller_options().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn all_args() -> Result<(), Box<dyn Error>> {
+        let input = r#"
+{
+  "name": "Properties",
+  "args": [
+    {
+      "type": "object",
+      "var": "$parameters.properties.securityProfile",
+      "options": ["security-profile"],
+      "args": [
+        {
+          "type": "boolean",
+          "var": "$parameters.properties.securityProfile.encryptionAtHost",
+          "options": ["e", "encryption-at-host"]
+        },
+        {
+          "type": "string",
+          "var": "$parameters.properties.securityProfile.zones[]",
+          "options": ["zones"]
+        }
+      ]
+    },
+    {
+      "type": "object",
+      "var": "$parameters.properties.hidden",
+      "options": ["hidden"],
+      "hide": true,
+      "args": [
+        {
+          "type": "string",
+          "var": "$parameters.properties.hidden.name",
+          "options": ["name"]
+        }
+      ]
+    }
+  ]
+}
+"#;
+        let ag: ArgGroup = serde_json::from_str(input)?;
+        let args = ag.all_args();
+        assert_eq!(
+            args.iter()
+                .map(|arg| arg.options.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                vec!["security-profile".to_string()],
+                vec!["security-profile.encryption-at-host".to_string()],
+                vec!["hidden".to_string()],
+            ]
+        );
+        assert!(args.iter().all(|arg| arg.args.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_body() -> Result<(), Box<dyn Error>> {
+        let schema: Schema = serde_json::from_str(
+            r#"
+{
+  "type": "object",
+  "props": [
+    {"type": "ResourceId", "name": "id", "readOnly": true},
+    {"type": "ResourceLocation", "name": "location", "required": true},
+    {
+      "type": "object",
+      "name": "properties",
+      "props": [
+        {"type": "integer32", "name": "count"},
+        {"type": "array<string>", "name": "zones", "item": {"type": "string"}}
+      ]
+    },
+    {"type": "object", "name": "tags", "additionalProps": {"item": {"type": "string"}}}
+  ]
+}
+"#,
+        )?;
+
+        let body = serde_json::json!({
+            "location": "westus",
+            "properties": {"count": 1, "zones": ["1"]},
+            "tags": {"foo": "bar"},
+        });
+        assert_eq!(schema.validate_body(&body, false), Vec::<String>::new());
+
+        let body = serde_json::json!({
+            "id": "/subscriptions/sub",
+            "properties": {"count": "1", "zone": "1", "zones": [1]},
+        });
+        assert_eq!(
+            schema.validate_body(&body, false),
+            vec![
+                "$.location: required property is missing",
+                "$.id: read-only property can't be set",
+                "$.properties.count: expect integer, got string",
+                "$.properties.zone: unknown property",
+                "$.properties.zones[0]: expect string, got integer",
+            ]
+        );
+
+        // The PATCH body can omit the required properties, but is validated otherwise.
+        let body = serde_json::json!({"id": "/subscriptions/sub", "properties": {"count": 1}});
+        assert_eq!(
+            schema.validate_body(&body, true),
+            vec!["$.id: read-only property can't be set"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn validate_polymorphic_body() -> Result<(), Box<dyn Error>> {
+        let schema: Schema = serde_json::from_str(
+            r#"
+{
+  "type": "object",
+  "props": [
+    {"type": "string", "name": "kind", "required": true},
+    {"type": "string", "name": "name"}
+  ],
+  "discriminators": [
+    {
+      "property": "kind",
+      "value": "Disk",
+      "props": [
+        {"type": "integer32", "name": "sizeGB", "required": true},
+        {"type": "password", "name": "key"}
+      ]
+    },
+    {
+      "property": "kind",
+      "value": "Blob",
+      "props": [
+        {"type": "string", "name": "container"},
+        {"type": "string", "name": "blobType"}
+      ],
+      "discriminators": [
+        {
+          "property": "blobType",
+          "value": "BlockBlob",
+          "props": [{"type": "integer32", "name": "blockSize"}]
+        }
+      ]
+    }
+  ]
+}
+"#,
+        )?;
+
+        let body = serde_json::json!({"kind": "Disk", "name": "foo", "sizeGB": 1});
+        assert_eq!(schema.validate_body(&body, false), Vec::<String>::new());
+
+        let body = serde_json::json!({"kind": "Disk", "sizeGB": "1", "container": "c"});
+        assert_eq!(
+            schema.validate_body(&body, false),
+            vec![
+                "$.sizeGB: expect integer, got string",
+                "$.container: unknown property",
+            ]
+        );
+
+        let body = serde_json::json!({"kind": "Disk"});
+        assert_eq!(
+            schema.validate_body(&body, false),
+            vec!["$.sizeGB: required property is missing"]
+        );
+
+        // The props of the unknown variant are not checked.
+        let body = serde_json::json!({"kind": "Queue", "queue": "q"});
+        assert_eq!(schema.validate_body(&body, false), Vec::<String>::new());
+        let body = serde_json::json!({"kind": "Blob", "blobType": "BlockBlob", "blockSize": 1});
+        assert_eq!(schema.validate_body(&body, false), Vec::<String>::new());
+        let body = serde_json::json!({"kind": "Blob", "blobType": "BlockBlob", "foo": 1});
+        assert_eq!(
+            schema.validate_body(&body, false),
+            vec!["$.foo: unknown property"]
+        );
+        let body = serde_json::json!({"kind": "Blob", "blobType": "PageBlob", "foo": 1});
+        assert_eq!(schema.validate_body(&body, false), Vec::<String>::new());
+
+        let mut secrets = BTreeSet::new();
+        schema.collect_secret_props("", &mut secrets);
+        assert_eq!(secrets, BTreeSet::from(["key".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    fn secret_props() -> Result<(), Box<dyn Error>> {
+        let schema: Schema = serde_json::from_str(
+            r#"
+{
+  "type": "object",
+  "props": [
+    {"type": "string", "name": "name"},
+    {
+      "type": "object",
+      "name": "properties",
+      "props": [
+        {"type": "password", "name": "adminPassword"},
+        {"type": "string", "name": "connectionString", "secret": true},
+        {
+          "type": "array<object>",
+          "name": "keys",
+          "item": {"type": "object", "props": [{"type": "string", "name": "value", "secret": true}]}
+        }
+      ]
+    }
+  ]
+}
+"#,
+        )?;
+        let mut props = BTreeSet::new();
+        schema.collect_secret_props("", &mut props);
+        assert_eq!(
+            props,
+            BTreeSet::from([
+                "properties.adminPassword".to_string(),
+                "properties.connectionString".to_string(),
+                "properties.keys.value".to_string(),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn select_operation_by_method() -> Result<(), Box<dyn Error>> {
+        let operation = |id: &str, method: &str, when: &str| {
+            serde_json::json!({
+                "operationId": id,
+                "when": [when],
+                "http": {
+                    "path": "/foo",
+                    "request": {"method": method, "path": {"params": []}, "query": {"consts": []}},
+                    "responses": []
+                }
+            })
+        };
+        let command: Command = serde_json::from_value(serde_json::json!({
+            "argGroups": [],
+            "operations": [
+                operation("Foo_Get", "get", "$Foo"),
+                operation("Foo_Update", "patch", "$Foo"),
+                operation("Bar_Update", "patch", "$Bar"),
+            ],
+            "resources": []
+        }))?;
+        let id = |op: Option<&Operation>| op.and_then(|op| op.operation_id.clone());
+        let foo = Some("$Foo".to_string());
+        let bar = Some("$Bar".to_string());
+        assert_eq!(
+            id(command.select_operation_by_method(foo.as_ref(), Method::Get)),
+            Some("Foo_Get".to_string())
+        );
+        assert_eq!(
+            id(command.select_operation_by_method(bar.as_ref(), Method::Patch)),
+            Some("Bar_Update".to_string())
+        );
+        assert!(
+            command
+                .select_operation_by_method(bar.as_ref(), Method::Get)
+                .is_none()
+        );
+        assert!(command.select_update_operations(foo.as_ref()).is_none());
+        Ok(())
+    }
 }
@@ -0,0 +1,532 @@
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use clap::builder::PossibleValue;
+use serde_json::Value;
+
+use super::editor;
+use super::metadata_command::Schema;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    Jsonc,
+    Yaml,
+    Hcl,
+    Table,
+    Tsv,
+    None,
+}
+
+impl OutputFormat {
+    pub fn variants() -> impl Iterator<Item = PossibleValue> {
+        [
+            PossibleValue::new("json").help("Pretty printed JSON"),
+            PossibleValue::new("jsonc").help("Colorized JSON"),
+            PossibleValue::new("yaml").help("YAML"),
+            PossibleValue::new("hcl").help("HCL"),
+            PossibleValue::new("table").help("Aligned table of the scalar properties"),
+            PossibleValue::new("tsv").help("Tab separated values of the scalar properties"),
+            PossibleValue::new("none").help("No output"),
+        ]
+        .into_iter()
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "jsonc" => Ok(OutputFormat::Jsonc),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "hcl" => Ok(OutputFormat::Hcl),
+            "table" => Ok(OutputFormat::Table),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "none" => Ok(OutputFormat::None),
+            _ => Err(anyhow!("invalid output format: {s}")),
+        }
+    }
+}
+
+// Column is a column of the table/tsv output, which is a path to a scalar property of each row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    header: String,
+    path: Vec<String>,
+}
+
+impl Column {
+    fn new(path: &[&str]) -> Self {
+        let name = path.last().unwrap_or(&"");
+        let mut chars = name.chars();
+        let header = chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+        Self {
+            header,
+            path: path.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn cell(&self, row: &Value) -> String {
+        let mut val = row;
+        for p in &self.path {
+            match val.get(p) {
+                Some(v) => val = v,
+                None => return "".to_string(),
+            }
+        }
+        match val {
+            Value::Null => "".to_string(),
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        }
+    }
+}
+
+pub struct Formatter {
+    format: Option<OutputFormat>,
+    columns: Option<Vec<Column>>,
+}
+
+impl Formatter {
+    // new creates a Formatter. The columns of the table/tsv output are determined by the response
+    // schema, if any. Otherwise, they are derived from the response body itself.
+    pub fn new(format: OutputFormat, schema: Option<&Schema>) -> Self {
+        let columns = schema
+            .map(columns_from_schema)
+            .filter(|columns| !columns.is_empty());
+        Self {
+            format: Some(format),
+            columns,
+        }
+    }
+
+    // raw creates a Formatter without an output format, which outputs the response body as is.
+    // This is the default, so that e.g. the compact JSON of each line of "--stdin" is kept.
+    pub fn raw() -> Self {
+        Self {
+            format: None,
+            columns: None,
+        }
+    }
+
+    // format renders the JSON response body. It returns None if nothing should be output.
+    // Response body that is empty or not in JSON is returned as is.
+    pub fn format(&self, body: &str) -> Result<Option<String>> {
+        let Some(format) = self.format else {
+            return Ok(Some(body.to_string()));
+        };
+        if format == OutputFormat::None {
+            return Ok(None);
+        }
+        let Ok(value) = serde_json::from_str::<Value>(body) else {
+            return Ok(Some(body.to_string()));
+        };
+        let out = match format {
+            OutputFormat::Json => serde_json::to_string_pretty(&value)?,
+            OutputFormat::Jsonc => {
+                let mut out = String::new();
+                colorize(&value, 0, &mut out);
+                out
+            }
+            OutputFormat::Yaml => serde_yaml::to_string(&value)?,
+            OutputFormat::Hcl => match editor::split(&value) {
+                // The object that can't be an HCL body (see editor::split) falls back to JSON.
+                Ok((_, others)) if !others.is_empty() => serde_json::to_string_pretty(&value)?,
+                Ok(_) => editor::render(&value)?,
+                Err(_) => hcl::format::to_string(&hcl::to_expression(&value)?)?,
+            },
+            OutputFormat::Table => {
+                let rows = rows(&value);
+                let columns = self.columns_for(&rows);
+                table(&columns, &rows)
+            }
+            OutputFormat::Tsv => {
+                let rows = rows(&value);
+                let columns = self.columns_for(&rows);
+                tsv(&columns, &rows)
+            }
+            OutputFormat::None => unreachable!(),
+        };
+        Ok(Some(out.trim_end_matches('\n').to_string()))
+    }
+
+    fn columns_for(&self, rows: &[&Value]) -> Vec<Column> {
+        self.columns
+            .clone()
+            .unwrap_or_else(|| columns_from_rows(rows))
+    }
+}
+
+fn is_scalar(schema: &Schema) -> bool {
+    schema.props.is_none()
+        && schema.item.is_none()
+        && schema.additional_props.is_none()
+        && !["object", "array"].contains(&schema.type_.as_str())
+        && !schema.type_.starts_with("@")
+}
+
+// columns_from_schema returns the columns of the scalar properties of the schema, together with
+// the scalar properties of the flattened (e.g. "properties") object properties.
+// For a list schema, the columns are built from its "value" item schema.
+fn columns_from_schema(schema: &Schema) -> Vec<Column> {
+    let Some(props) = schema.props.as_ref() else {
+        return vec![];
+    };
+    if let Some(item) = props
+        .iter()
+        .find(|prop| prop.name.as_deref() == Some("value"))
+        .and_then(|prop| prop.item.as_ref())
+    {
+        return columns_from_schema(item);
+    }
+
+    let mut columns = vec![];
+    for prop in props {
+        let Some(name) = prop.name.as_deref() else {
+            continue;
+        };
+        if is_scalar(prop) {
+            columns.push(Column::new(&[name]));
+        } else if name == "properties" || prop.client_flatten.unwrap_or(false) {
+            for sub_prop in prop.props.iter().flatten() {
+                if let Some(sub_name) = sub_prop.name.as_deref() {
+                    if is_scalar(sub_prop) {
+                        columns.push(Column::new(&[name, sub_name]));
+                    }
+                }
+            }
+        }
+    }
+    columns
+}
+
+// columns_from_rows returns the columns of the scalar properties of the first row.
+fn columns_from_rows(rows: &[&Value]) -> Vec<Column> {
+    match rows.first() {
+        Some(Value::Object(map)) => map
+            .iter()
+            .filter(|(_, v)| !v.is_object() && !v.is_array())
+            .map(|(k, _)| Column::new(&[k.as_str()]))
+            .collect(),
+        Some(_) => vec![Column {
+            header: "Result".to_string(),
+            path: vec![],
+        }],
+        None => vec![],
+    }
+}
+
+// rows returns the rows of a value, which is either the "value" array of a list response,
+// the items of an array, or the value itself.
+fn rows(value: &Value) -> Vec<&Value> {
+    if let Some(Value::Array(values)) = value.get("value") {
+        return values.iter().collect();
+    }
+    match value {
+        Value::Array(values) => values.iter().collect(),
+        v => vec![v],
+    }
+}
+
+fn table(columns: &[Column], rows: &[&Value]) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|c| c.cell(row)).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([c.header.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |cells: Vec<String>| -> String {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut lines = vec![
+        line(columns.iter().map(|c| c.header.clone()).collect()),
+        line(widths.iter().map(|w| "-".repeat(*w)).collect()),
+    ];
+    lines.extend(cells.into_iter().map(line));
+    lines.join("\n")
+}
+
+fn tsv(columns: &[Column], rows: &[&Value]) -> String {
+    rows.iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|c| c.cell(row))
+                .collect::<Vec<_>>()
+                .join("\t")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const COLOR_KEY: &str = "\x1b[94m";
+const COLOR_STRING: &str = "\x1b[32m";
+const COLOR_LITERAL: &str = "\x1b[36m";
+const COLOR_NULL: &str = "\x1b[90m";
+const COLOR_RESET: &str = "\x1b[0m";
+
+// colorize writes the value as pretty printed JSON, with ANSI colors.
+fn colorize(value: &Value, indent: usize, out: &mut String) {
+    let pad = "  ".repeat(indent + 1);
+    let end_pad = "  ".repeat(indent);
+    match value {
+        Value::Null => out.push_str(&format!("{COLOR_NULL}null{COLOR_RESET}")),
+        Value::Bool(_) | Value::Number(_) => {
+            out.push_str(&format!("{COLOR_LITERAL}{value}{COLOR_RESET}"))
+        }
+        Value::String(_) => out.push_str(&format!("{COLOR_STRING}{value}{COLOR_RESET}")),
+        Value::Array(values) => {
+            if values.is_empty() {
+                out.push_str("[]");
+                return;
+            }
+            out.push_str("[\n");
+            for (i, v) in values.iter().enumerate() {
+                out.push_str(&pad);
+                colorize(v, indent + 1, out);
+                if i + 1 != values.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&end_pad);
+            out.push(']');
+        }
+        Value::Object(map) => {
+            if map.is_empty() {
+                out.push_str("{}");
+                return;
+            }
+            out.push_str("{\n");
+            for (i, (k, v)) in map.iter().enumerate() {
+                out.push_str(&pad);
+                out.push_str(&format!(
+                    "{COLOR_KEY}{}{COLOR_RESET}: ",
+                    Value::from(k.as_str())
+                ));
+                colorize(v, indent + 1, out);
+                if i + 1 != map.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&end_pad);
+            out.push('}');
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::metadata_command::{AdditionalPropItemSchema, AdditionalPropSchema};
+    use pretty_assertions::assert_eq;
+
+    fn schema() -> Schema {
+        let item = Schema {
+            type_: "object".to_string(),
+            props: Some(vec![
+                Schema {
+                    type_: "ResourceId".to_string(),
+                    name: Some("id".to_string()),
+                    ..Schema::default()
+                },
+                Schema {
+                    type_: "ResourceLocation".to_string(),
+                    name: Some("location".to_string()),
+                    ..Schema::default()
+                },
+                Schema {
+                    type_: "string".to_string(),
+                    name: Some("name".to_string()),
+                    ..Schema::default()
+                },
+                Schema {
+                    type_: "object".to_string(),
+                    name: Some("properties".to_string()),
+                    props: Some(vec![Schema {
+                        type_: "string".to_string(),
+                        name: Some("provisioningState".to_string()),
+                        ..Schema::default()
+                    }]),
+                    ..Schema::default()
+                },
+                Schema {
+                    type_: "object".to_string(),
+                    name: Some("tags".to_string()),
+                    additional_props: Some(AdditionalPropSchema {
+                        item: AdditionalPropItemSchema {
+                            type_: "string".to_string(),
+                        },
+                    }),
+                    ..Schema::default()
+                },
+            ]),
+            ..Schema::default()
+        };
+        Schema {
+            type_: "object".to_string(),
+            props: Some(vec![
+                Schema {
+                    type_: "array".to_string(),
+                    name: Some("value".to_string()),
+                    item: Some(Box::new(item)),
+                    ..Schema::default()
+                },
+                Schema {
+                    type_: "string".to_string(),
+                    name: Some("nextLink".to_string()),
+                    ..Schema::default()
+                },
+            ]),
+            ..Schema::default()
+        }
+    }
+
+    const BODY: &str = r#"
+{
+  "value": [
+    {
+      "id": "/subscriptions/xxx/resourceGroups/rg1",
+      "location": "westus",
+      "name": "rg1",
+      "properties": {"provisioningState": "Succeeded"},
+      "tags": {"a": "b"}
+    },
+    {
+      "id": "/subscriptions/xxx/resourceGroups/resource-group2",
+      "location": "eastus",
+      "name": "resource-group2",
+      "properties": {"provisioningState": "Deleting"}
+    }
+  ]
+}
+"#;
+
+    #[test]
+    fn columns_from_schema() {
+        assert_eq!(
+            super::columns_from_schema(&schema())
+                .iter()
+                .map(|c| c.header.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Id", "Location", "Name", "ProvisioningState"]
+        );
+    }
+
+    #[test]
+    fn format_table() {
+        let formatter = Formatter::new(OutputFormat::Table, Some(&schema()));
+        assert_eq!(
+            formatter.format(BODY).unwrap().unwrap(),
+            r#"
+Id                                                 Location  Name             ProvisioningState
+-------------------------------------------------  --------  ---------------  -----------------
+/subscriptions/xxx/resourceGroups/rg1              westus    rg1              Succeeded
+/subscriptions/xxx/resourceGroups/resource-group2  eastus    resource-group2  Deleting"#
+                .trim_start()
+        );
+    }
+
+    #[test]
+    fn format_tsv() {
+        let formatter = Formatter::new(OutputFormat::Tsv, None);
+        assert_eq!(
+            formatter
+                .format(r#"[{"a": 1, "b": "x", "c": {}}, {"a": 2}]"#)
+                .unwrap()
+                .unwrap(),
+            "1\tx\n2\t"
+        );
+        assert_eq!(formatter.format(r#"["x", "y"]"#).unwrap().unwrap(), "x\ny");
+    }
+
+    #[test]
+    fn format_raw() {
+        for body in [
+            r#"{"name":"foo","tags":{"a":"b"}}"#,
+            "{\n  \"name\": \"foo\"\n}\n",
+            "not json",
+            "",
+        ] {
+            assert_eq!(Formatter::raw().format(body).unwrap().unwrap(), body);
+        }
+    }
+
+    #[test]
+    fn format_others() {
+        let body = r#"{"name": "foo", "tags": {"a": "b"}, "zones": [1]}"#;
+        assert_eq!(
+            Formatter::new(OutputFormat::Json, None)
+                .format(body)
+                .unwrap()
+                .unwrap(),
+            "{\n  \"name\": \"foo\",\n  \"tags\": {\n    \"a\": \"b\"\n  },\n  \"zones\": [\n    1\n  ]\n}"
+        );
+        assert_eq!(
+            Formatter::new(OutputFormat::Yaml, None)
+                .format(body)
+                .unwrap()
+                .unwrap(),
+            "name: foo\ntags:\n  a: b\nzones:\n- 1"
+        );
+        assert_eq!(
+            Formatter::new(OutputFormat::Jsonc, None)
+                .format(r#"{"a": [null, true, "x"]}"#)
+                .unwrap()
+                .unwrap(),
+            "{\n  \x1b[94m\"a\"\x1b[0m: [\n    \x1b[90mnull\x1b[0m,\n    \x1b[36mtrue\x1b[0m,\n    \x1b[32m\"x\"\x1b[0m\n  ]\n}"
+        );
+        assert_eq!(
+            Formatter::new(OutputFormat::Hcl, None)
+                .format(body)
+                .unwrap()
+                .unwrap(),
+            "name = \"foo\"\ntags {\n  a = \"b\"\n}\nzones = [1]"
+        );
+        assert_eq!(
+            Formatter::new(OutputFormat::Hcl, None)
+                .format(r#"{"@odata.type": "foo", "name": "bar"}"#)
+                .unwrap()
+                .unwrap(),
+            "{\n  \"@odata.type\": \"foo\",\n  \"name\": \"bar\"\n}"
+        );
+        assert_eq!(
+            Formatter::new(OutputFormat::None, None)
+                .format(body)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            Formatter::new(OutputFormat::Json, None)
+                .format("")
+                .unwrap()
+                .unwrap(),
+            ""
+        );
+    }
+}
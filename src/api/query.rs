@@ -0,0 +1,48 @@
+use anyhow::{Context, Result, anyhow};
+
+// validate validates the JMESPath expression, used as the clap value parser of the "--query" option.
+pub fn validate(expr: &str) -> Result<String, String> {
+    jmespath::compile(expr)
+        .map(|_| expr.to_string())
+        .map_err(|e| e.to_string())
+}
+
+// apply runs the JMESPath expression over the JSON response body, and returns the result in JSON.
+// An empty response body (e.g. the response of a DELETE) is returned as is.
+pub fn apply(expr: &str, body: &str) -> Result<String> {
+    if body.trim().is_empty() {
+        return Ok(body.to_string());
+    }
+    let expr = jmespath::compile(expr).map_err(|e| anyhow!("compiling the query: {e}"))?;
+    let data: serde_json::Value =
+        serde_json::from_str(body).context("parsing the response body as JSON")?;
+    let result = expr
+        .search(data)
+        .map_err(|e| anyhow!("running the query: {e}"))?;
+    Ok(serde_json::to_string(&*result)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validate_expr() {
+        assert!(validate("value[].name").is_ok());
+        assert!(validate("value[?name=='foo'].{n: name, l: location}").is_ok());
+        assert!(validate("value[").is_err());
+    }
+
+    #[test]
+    fn apply_expr() {
+        let body = r#"{"value": [{"name": "a", "location": "westus"}, {"name": "b", "location": "eastus"}]}"#;
+        assert_eq!(apply("value[].name", body).unwrap(), r#"["a","b"]"#);
+        assert_eq!(
+            apply("value[?location=='eastus'] | [0].name", body).unwrap(),
+            r#""b""#
+        );
+        assert_eq!(apply("foo", body).unwrap(), "null");
+        assert_eq!(apply("foo", "").unwrap(), "");
+        assert!(apply("foo", "not json").is_err());
+    }
+}
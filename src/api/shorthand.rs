@@ -0,0 +1,375 @@
+// The shorthand syntax for the object and array argument values, as an alternative to JSON:
+//
+// - `a=b c=d`: An object with the space separated key/value pairs
+// - `{k:v,list:[1,2],obj:{a:b}}`: An object, whose values can be nested objects or arrays
+// - `[a,b]`: An array
+// - `'a b'` or `"a b"`: A quoted string, with `\` as the escape character
+// - `??`: The null value
+// - `@file.json`: The content of the JSON file
+//
+// The scalars are parsed as strings, which are then converted to their types by coerce, based on
+// the schema.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{Map, Value};
+
+use super::arg_type::ArgType;
+use super::metadata_command::Schema;
+
+const NULL: &str = "??";
+
+// parse parses the argument value in shorthand syntax, or in JSON, to JSON.
+pub fn parse(input: &str) -> Result<Value> {
+    let input = input.trim();
+    if let Some(path) = input.strip_prefix('@') {
+        let content = std::fs::read_to_string(path).context(format!("reading the file {path}"))?;
+        return serde_json::from_str(&content).context(format!("parsing the file {path} as JSON"));
+    }
+    if input == NULL {
+        return Ok(Value::Null);
+    }
+    if input.starts_with('{') || input.starts_with('[') {
+        if let Ok(v) = serde_json::from_str(input) {
+            return Ok(v);
+        }
+        let mut parser = Parser::new(input);
+        let v = parser.parse_value(&[])?;
+        parser.skip_whitespace();
+        if !parser.is_end() {
+            bail!("unexpected trailing characters at position {}", parser.pos);
+        }
+        return Ok(v);
+    }
+    if input.contains('=') {
+        return Parser::new(input).parse_pairs();
+    }
+    Ok(Value::String(input.to_string()))
+}
+
+// coerce converts the string scalars in the value to the types defined in the schema.
+pub fn coerce(value: Value, schema: &Schema) -> Result<Value> {
+    match value {
+        Value::String(s) => {
+            let arg_type = ArgType::from_metadata(&schema.type_);
+            match arg_type {
+                ArgType::Array | ArgType::Object => Ok(Value::String(s)),
+                _ => arg_type.to_json(&s),
+            }
+        }
+        Value::Array(values) => {
+            let Some(item) = &schema.item else {
+                return Ok(Value::Array(values));
+            };
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| coerce(v, item).context(format!("[{i}]")))
+                .collect::<Result<Vec<_>>>()
+                .map(Value::Array)
+        }
+        Value::Object(map) => {
+            let mut out = Map::new();
+            for (k, v) in map {
+                let prop = schema
+                    .props
+                    .iter()
+                    .flatten()
+                    .find(|prop| prop.name.as_ref() == Some(&k));
+                let v = if let Some(prop) = prop {
+                    coerce(v, prop).context(format!(".{k}"))?
+                } else if let Some(additional_props) = &schema.additional_props {
+                    let item = Schema {
+                        type_: additional_props.item.type_.clone(),
+                        ..Schema::default()
+                    };
+                    coerce(v, &item).context(format!(".{k}"))?
+                } else {
+                    v
+                };
+                out.insert(k, v);
+            }
+            Ok(Value::Object(out))
+        }
+        v => Ok(v),
+    }
+}
+
+// to_shorthand formats the JSON value in the shorthand syntax.
+pub fn to_shorthand(value: &Value) -> String {
+    match value {
+        Value::Null => NULL.to_string(),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+        Value::String(s) => quote(s),
+        Value::Array(values) => format!(
+            "[{}]",
+            values
+                .iter()
+                .map(to_shorthand)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Value::Object(map) => format!(
+            "{{{}}}",
+            map.iter()
+                .map(|(k, v)| format!("{}:{}", quote(k), to_shorthand(v)))
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+    }
+}
+
+// quote quotes the string with single quotes, if it contains any special character.
+fn quote(s: &str) -> String {
+    let special = |c: char| "{}[],:='\"\\@".contains(c) || c.is_whitespace();
+    if !s.is_empty() && s != NULL && !s.contains(special) {
+        return s.to_string();
+    }
+    let mut out = String::from("'");
+    for c in s.chars() {
+        if c == '\'' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('\'');
+    out
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn is_end(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expect: char) -> Result<()> {
+        match self.peek() {
+            Some(c) if c == expect => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => bail!("expect {expect:?} at position {}, got {c:?}", self.pos),
+            None => bail!("expect {expect:?}, got the end of input"),
+        }
+    }
+
+    // parse_pairs parses the space separated key=value pairs as an object.
+    fn parse_pairs(&mut self) -> Result<Value> {
+        let mut map = Map::new();
+        loop {
+            self.skip_whitespace();
+            if self.is_end() {
+                return Ok(Value::Object(map));
+            }
+            let key = self.parse_key('=')?;
+            self.expect('=')?;
+            let value = self.parse_value(&[' ', '\t', '\n'])?;
+            map.insert(key, value);
+        }
+    }
+
+    // parse_value parses a value, which ends by any of the terminators (or the end of input) if
+    // it's a scalar.
+    fn parse_value(&mut self, terminators: &[char]) -> Result<Value> {
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('\'') | Some('"') => Ok(Value::String(self.parse_quoted()?)),
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| !terminators.contains(&c)) {
+                    self.pos += 1;
+                }
+                let s: String = self.chars[start..self.pos].iter().collect();
+                let s = s.trim();
+                if s == NULL {
+                    Ok(Value::Null)
+                } else {
+                    Ok(Value::String(s.to_string()))
+                }
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut map = Map::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                return Ok(Value::Object(map));
+            }
+            let key = self.parse_key(':')?;
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.parse_value(&[',', '}'])?;
+            map.insert(key, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {}
+                _ => bail!("expect ',' or '}}' at position {}", self.pos),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut values = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(values));
+            }
+            values.push(self.parse_value(&[',', ']'])?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {}
+                _ => bail!("expect ',' or ']' at position {}", self.pos),
+            }
+        }
+    }
+
+    fn parse_key(&mut self, separator: char) -> Result<String> {
+        self.skip_whitespace();
+        if matches!(self.peek(), Some('\'') | Some('"')) {
+            let key = self.parse_quoted()?;
+            self.skip_whitespace();
+            return Ok(key);
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != separator) {
+            self.pos += 1;
+        }
+        let key: String = self.chars[start..self.pos].iter().collect();
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(anyhow!("empty key at position {start}"));
+        }
+        Ok(key.to_string())
+    }
+
+    fn parse_quoted(&mut self) -> Result<String> {
+        let quote = self.peek().unwrap();
+        let start = self.pos;
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            match self.peek() {
+                None => bail!("unterminated string starting at position {start}"),
+                Some('\\') => {
+                    self.pos += 1;
+                    let Some(c) = self.peek() else {
+                        bail!("unterminated string starting at position {start}");
+                    };
+                    out.push(c);
+                }
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(c) => out.push(c),
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn parse_shorthand() {
+        assert_eq!(parse("a=b c=d").unwrap(), json!({"a": "b", "c": "d"}));
+        assert_eq!(
+            parse("a={x:1} b=[1,2]").unwrap(),
+            json!({"a": {"x": "1"}, "b": ["1", "2"]})
+        );
+        assert_eq!(
+            parse("{k:v,list:[1,2],obj:{a:'b c'},n:??}").unwrap(),
+            json!({"k": "v", "list": ["1", "2"], "obj": {"a": "b c"}, "n": null})
+        );
+        assert_eq!(
+            parse("{ k : v , 'a:b' : 'it\\'s' }").unwrap(),
+            json!({"k": "v", "a:b": "it's"})
+        );
+        assert_eq!(parse("[a, [b], {}]").unwrap(), json!(["a", ["b"], {}]));
+        assert_eq!(parse(r#"{"a": 1}"#).unwrap(), json!({"a": 1}));
+        assert_eq!(parse("??").unwrap(), Value::Null);
+        assert_eq!(parse("abc").unwrap(), json!("abc"));
+        assert!(parse("{a:b").is_err());
+        assert!(parse("{a:b}c").is_err());
+        assert!(parse("{a:'b}").is_err());
+    }
+
+    #[test]
+    fn parse_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, br#"{"a": [1]}"#).unwrap();
+        let input = format!("@{}", file.path().display());
+        assert_eq!(parse(&input).unwrap(), json!({"a": [1]}));
+    }
+
+    #[test]
+    fn coerce_by_schema() {
+        let schema: Schema = serde_json::from_str(
+            r#"
+{
+  "type": "object",
+  "props": [
+    {"type": "integer32", "name": "count"},
+    {"type": "boolean", "name": "enabled"},
+    {"type": "string", "name": "name"},
+    {"type": "array<integer>", "name": "ports", "item": {"type": "integer"}},
+    {"type": "object", "name": "tags", "additionalProps": {"item": {"type": "string"}}}
+  ]
+}
+"#,
+        )
+        .unwrap();
+        let value =
+            parse("{count:1,enabled:true,name:2,ports:[80,443],tags:{a:1},other:x}").unwrap();
+        assert_eq!(
+            coerce(value, &schema).unwrap(),
+            json!({"count": 1, "enabled": true, "name": "2", "ports": [80, 443], "tags": {"a": "1"}, "other": "x"})
+        );
+        assert!(coerce(parse("{count:x}").unwrap(), &schema).is_err());
+    }
+
+    #[test]
+    fn format_shorthand() {
+        let value = json!({"a": "b c", "d": [1, true, null], "e": {"f": "it's"}, "g": ""});
+        let shorthand = to_shorthand(&value);
+        assert_eq!(shorthand, r#"{a:'b c',d:[1,true,??],e:{f:'it\'s'},g:''}"#);
+        assert_eq!(
+            parse(&shorthand).unwrap(),
+            json!({"a": "b c", "d": ["1", "true", null], "e": {"f": "it's"}, "g": ""})
+        );
+    }
+}
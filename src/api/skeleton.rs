@@ -0,0 +1,164 @@
+// The request body skeleton generated from the request schema, as the template for "--edit" and
+// "--file". The read-only properties are skipped.
+//
+// In HCL, the required properties are present with typed placeholders, while the optional ones are
+// commented out. Each property is annotated with its description.
+// In JSON, only the required properties are present, as there is no comment.
+
+use serde_json::{Map, Value};
+
+use super::arg_type::ArgType;
+use super::metadata_command::Schema;
+
+// to_hcl generates the HCL skeleton of the object schema.
+pub fn to_hcl(schema: &Schema) -> String {
+    let mut out = vec![];
+    write_props(schema, &mut out);
+    out.join("\n")
+}
+
+// to_json generates the JSON skeleton of the schema.
+pub fn to_json(schema: &Schema) -> Value {
+    match ArgType::from_metadata(&schema.type_) {
+        ArgType::Object if schema.props.is_some() => {
+            let map: Map<String, Value> = writable_props(schema)
+                .filter(|prop| prop.required.unwrap_or(false))
+                .filter_map(|prop| Some((prop.name.clone()?, to_json(prop))))
+                .collect();
+            Value::Object(map)
+        }
+        ArgType::Boolean => false.into(),
+        ArgType::Integer => 0.into(),
+        ArgType::Float => 0.0.into(),
+        ArgType::Array => Value::Array(vec![]),
+        ArgType::Object => Value::Object(Map::new()),
+        _ => format!("<{}>", schema.type_).into(),
+    }
+}
+
+fn writable_props(schema: &Schema) -> impl Iterator<Item = &Schema> {
+    schema
+        .props
+        .iter()
+        .flatten()
+        .filter(|prop| !prop.read_only.unwrap_or(false))
+}
+
+fn write_props(schema: &Schema, out: &mut Vec<String>) {
+    for (i, prop) in writable_props(schema).enumerate() {
+        let Some(name) = &prop.name else {
+            continue;
+        };
+        if i != 0 {
+            out.push("".to_string());
+        }
+        for line in prop.description.iter().flat_map(|desc| desc.lines()) {
+            out.push(format!("// {line}"));
+        }
+
+        let mut lines = vec![];
+        if ArgType::from_metadata(&prop.type_) == ArgType::Object && prop.props.is_some() {
+            lines.push(format!("{name} {{"));
+            let mut nested = vec![];
+            write_props(prop, &mut nested);
+            lines.extend(nested.into_iter().map(|line| {
+                if line.is_empty() {
+                    line
+                } else {
+                    format!("  {line}")
+                }
+            }));
+            lines.push("}".to_string());
+        } else {
+            lines.push(format!("{name} = {}", placeholder(prop)));
+        }
+
+        if prop.required.unwrap_or(false) {
+            out.extend(lines);
+        } else {
+            out.extend(lines.into_iter().map(|line| {
+                if line.is_empty() {
+                    "#".to_string()
+                } else {
+                    format!("# {line}")
+                }
+            }));
+        }
+    }
+}
+
+fn placeholder(schema: &Schema) -> String {
+    match ArgType::from_metadata(&schema.type_) {
+        ArgType::Boolean => "false".to_string(),
+        ArgType::Integer => "0".to_string(),
+        ArgType::Float => "0.0".to_string(),
+        ArgType::Array => "[]".to_string(),
+        ArgType::Object => "{}".to_string(),
+        _ => format!(r#""<{}>""#, schema.type_),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn schema() -> Schema {
+        serde_json::from_str(
+            r#"
+{
+  "type": "object",
+  "props": [
+    {"type": "ResourceId", "name": "id", "readOnly": true},
+    {"type": "ResourceLocation", "name": "location", "description": "The location.", "required": true},
+    {
+      "type": "object",
+      "name": "properties",
+      "required": true,
+      "props": [
+        {"type": "integer32", "name": "count", "required": true},
+        {"type": "array<string>", "name": "zones", "description": "The zones.", "item": {"type": "string"}}
+      ]
+    },
+    {"type": "object", "name": "tags", "additionalProps": {"item": {"type": "string"}}}
+  ]
+}
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn hcl_skeleton() {
+        let skeleton = to_hcl(&schema());
+        assert_eq!(
+            skeleton,
+            r#"// The location.
+location = "<ResourceLocation>"
+
+properties {
+  count = 0
+
+  // The zones.
+  # zones = []
+}
+
+# tags = {}"#
+        );
+        let body = hcl::parse(&skeleton).unwrap();
+        let v: Value = hcl::from_body(body).unwrap();
+        assert_eq!(
+            v,
+            json!({"location": "<ResourceLocation>", "properties": {"count": 0}})
+        );
+    }
+
+    #[test]
+    fn json_skeleton() {
+        assert_eq!(
+            to_json(&schema()),
+            json!({"location": "<ResourceLocation>", "properties": {"count": 0}})
+        );
+    }
+}
@@ -0,0 +1,496 @@
+// The generic update of a resource (i.e. GET-modify-PUT), where the current resource is modified by
+// the edits on its JSON paths:
+//
+// - `--set properties.x=1`: Set the value at the path, creating the absent parent objects
+// - `--add tags k=v`: Add the value to the array, or merge the object into the object, at the path
+// - `--remove properties.y`, `--remove properties.list[0]`: Remove the property or the array item
+//
+// If the schema of the edited path is known, the values are converted by their types as the body
+// arguments (in either JSON or the shorthand syntax), e.g. `--set tags.env=1` sets the string "1"
+// rather than the number. Otherwise, the values are parsed as JSON, or else kept as strings (e.g.
+// `--set properties.key=abc==`), except that the value added to an object (or to an absent path)
+// is parsed in the shorthand syntax, e.g. `--add tags k=v`.
+
+use std::fmt::Display;
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ArgMatches;
+use serde_json::{Map, Value};
+
+use super::arg_type::ArgType;
+use super::metadata_command::Schema;
+use super::shorthand;
+use crate::cmd::{ADD_OPTION, REMOVE_OPTION, SET_OPTION};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Segment::Key(k) => write!(f, "{k:?}"),
+            Segment::Index(i) => write!(f, "[{i}]"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Set(Vec<Segment>, Value),
+    Add(Vec<Segment>, Value),
+    Remove(Vec<Segment>),
+}
+
+impl Edit {
+    // from_matches collects the edits from the command line, in the order they are specified. The
+    // schema is the one of the request body, if any.
+    pub fn from_matches(matches: &ArgMatches, schema: Option<&Schema>) -> Result<Vec<Edit>> {
+        let mut edits = vec![];
+        for option in [SET_OPTION, ADD_OPTION, REMOVE_OPTION] {
+            let Some(occurrences) = matches.try_get_occurrences::<String>(option).ok().flatten()
+            else {
+                continue;
+            };
+            // The indices are per value, e.g. two for each "--add PATH VALUE".
+            let mut indices = matches.indices_of(option).into_iter().flatten();
+            for values in occurrences {
+                let values: Vec<_> = values.map(String::as_str).collect();
+                let index = indices.by_ref().take(values.len()).last();
+                let edit =
+                    Self::parse(option, &values, schema).context(format!("parsing --{option}"))?;
+                edits.push((index, edit));
+            }
+        }
+        edits.sort_by_key(|(index, _)| *index);
+        Ok(edits.into_iter().map(|(_, edit)| edit).collect())
+    }
+
+    // parse parses the values of one occurrence of the option, i.e. "PATH=VALUE" of "--set", "PATH
+    // VALUE" of "--add" and "PATH" of "--remove".
+    fn parse(option: &str, values: &[&str], schema: Option<&Schema>) -> Result<Self> {
+        let (path, value) = match (option, values) {
+            (REMOVE_OPTION, [path]) => return Ok(Self::Remove(parse_path(path)?)),
+            (ADD_OPTION, [path, value]) => (*path, *value),
+            (SET_OPTION, [input]) => input
+                .split_once('=')
+                .ok_or(anyhow!(r#"expect "PATH=VALUE", got {input:?}"#))?,
+            _ => bail!("unexpected values {values:?}"),
+        };
+        let path = parse_path(path)?;
+        let target = schema.and_then(|schema| lookup_schema(schema, &path));
+        // The added value is an item of the array, or the object merged into the object.
+        let value_schema = match target {
+            Some(target) if option == ADD_OPTION => match ArgType::from_metadata(&target.type_) {
+                ArgType::Array => target.item.map(|item| *item),
+                ArgType::Object => Some(target),
+                _ => None,
+            },
+            target => target,
+        };
+        let value = match value_schema {
+            Some(schema) => {
+                let v = ArgType::from_metadata(&schema.type_).to_json(value)?;
+                shorthand::coerce(v, &schema)?
+            }
+            None => {
+                serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+            }
+        };
+        if option == SET_OPTION {
+            Ok(Self::Set(path, value))
+        } else {
+            Ok(Self::Add(path, value))
+        }
+    }
+
+    pub fn apply(&self, body: &mut Value) -> Result<()> {
+        match self {
+            Self::Set(path, value) => {
+                *lookup_mut(body, path, true)? = value.clone();
+            }
+            Self::Add(path, value) => {
+                let target = lookup_mut(body, path, true)?;
+                match target {
+                    Value::Array(values) => values.push(value.clone()),
+                    Value::Object(map) => {
+                        // The string is only known to be the key/value pairs by the target.
+                        let value = match value {
+                            Value::String(s) => shorthand::parse(s)?,
+                            value => value.clone(),
+                        };
+                        let Value::Object(value) = value else {
+                            bail!("only an object can be added to an object");
+                        };
+                        map.extend(value);
+                    }
+                    // The absent value is created as an object if the key/value pairs are added,
+                    // otherwise, as an array.
+                    Value::Null => {
+                        *target = match value {
+                            Value::Object(_) => value.clone(),
+                            Value::String(s) if s.contains('=') => match shorthand::parse(s) {
+                                Ok(Value::Object(map)) => Value::Object(map),
+                                _ => Value::Array(vec![value.clone()]),
+                            },
+                            _ => Value::Array(vec![value.clone()]),
+                        }
+                    }
+                    _ => bail!("can only add to an array or an object"),
+                }
+            }
+            Self::Remove(path) => {
+                let (last, parent) = path.split_last().expect("the path is never empty");
+                let parent = lookup_mut(body, parent, false)?;
+                match last {
+                    Segment::Key(k) => {
+                        let Value::Object(map) = parent else {
+                            bail!("can't look up {last} on a non-object value");
+                        };
+                        map.remove(k).ok_or(anyhow!("property {last} not found"))?;
+                    }
+                    Segment::Index(i) => {
+                        let Value::Array(values) = parent else {
+                            bail!("can't look up {last} on a non-array value");
+                        };
+                        if *i >= values.len() {
+                            bail!("index {i} out of range (length {})", values.len());
+                        }
+                        values.remove(*i);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// apply applies the edits to the body in order.
+pub fn apply(body: &mut Value, edits: &[Edit]) -> Result<()> {
+    for edit in edits {
+        edit.apply(body).context(format!("applying {edit:?}"))?;
+    }
+    Ok(())
+}
+
+// merge merges the patch into the body, where the objects are merged recursively and the other
+// values are replaced.
+pub fn merge(body: &mut Value, patch: Value) {
+    match (body, patch) {
+        (Value::Object(body), Value::Object(patch)) => {
+            for (k, v) in patch {
+                match body.get_mut(&k) {
+                    Some(old) => merge(old, v),
+                    None => {
+                        body.insert(k, v);
+                    }
+                }
+            }
+        }
+        (body, patch) => *body = patch,
+    }
+}
+
+// parse_path parses the JSON path, e.g. "properties.list[0].name".
+fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    let mut segments = vec![];
+    for part in path.split('.') {
+        let key_end = part.find('[').unwrap_or(part.len());
+        let key = &part[..key_end];
+        if !key.is_empty() {
+            segments.push(Segment::Key(key.to_string()));
+        } else if key_end == part.len() {
+            bail!("empty property name in the path {path:?}");
+        }
+        let mut rest = &part[key_end..];
+        while let Some(index) = rest.strip_prefix('[') {
+            let (index, remain) = index
+                .split_once(']')
+                .ok_or(anyhow!(r#"unclosed "[" in the path {path:?}"#))?;
+            let index = index
+                .parse()
+                .context(format!("invalid index {index:?} in the path {path:?}"))?;
+            segments.push(Segment::Index(index));
+            rest = remain;
+        }
+        if !rest.is_empty() {
+            bail!("invalid path {path:?}");
+        }
+    }
+    Ok(segments)
+}
+
+// lookup_schema looks up the schema of the value at the path, or None if it is not defined.
+fn lookup_schema(schema: &Schema, path: &[Segment]) -> Option<Schema> {
+    let mut schema = schema.clone();
+    for segment in path {
+        schema = match segment {
+            Segment::Key(k) => {
+                let prop = schema
+                    .props
+                    .iter()
+                    .flatten()
+                    .find(|prop| prop.name.as_ref() == Some(k));
+                match (prop, &schema.additional_props) {
+                    (Some(prop), _) => prop.clone(),
+                    (None, Some(additional_props)) => Schema {
+                        type_: additional_props.item.type_.clone(),
+                        ..Schema::default()
+                    },
+                    (None, None) => return None,
+                }
+            }
+            Segment::Index(_) => *schema.item?,
+        };
+    }
+    Some(schema)
+}
+
+// lookup_mut looks up the value at the path. If create is true, the absent properties are created
+// as null, and the null parents are turned into objects.
+fn lookup_mut<'a>(
+    mut value: &'a mut Value,
+    path: &[Segment],
+    create: bool,
+) -> Result<&'a mut Value> {
+    for segment in path {
+        if create && value.is_null() && matches!(segment, Segment::Key(_)) {
+            *value = Value::Object(Map::new());
+        }
+        value = match segment {
+            Segment::Key(k) => match value {
+                Value::Object(map) if create => map.entry(k.clone()).or_insert(Value::Null),
+                Value::Object(map) => map
+                    .get_mut(k)
+                    .ok_or(anyhow!("property {segment} not found"))?,
+                _ => bail!("can't look up {segment} on a non-object value"),
+            },
+            Segment::Index(i) => match value {
+                Value::Array(values) => {
+                    let len = values.len();
+                    values
+                        .get_mut(*i)
+                        .ok_or(anyhow!("index {i} out of range (length {len})"))?
+                }
+                _ => bail!("can't look up {segment} on a non-array value"),
+            },
+        };
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn parse_edit() {
+        assert_eq!(
+            Edit::parse(SET_OPTION, &["properties.list[0][1].x=1"], None).unwrap(),
+            Edit::Set(
+                vec![
+                    Segment::Key("properties".to_string()),
+                    Segment::Key("list".to_string()),
+                    Segment::Index(0),
+                    Segment::Index(1),
+                    Segment::Key("x".to_string()),
+                ],
+                json!(1)
+            )
+        );
+        // Without the schema, the non-JSON values are kept as strings.
+        assert_eq!(
+            Edit::parse(SET_OPTION, &["properties.key=abc=="], None).unwrap(),
+            Edit::Set(
+                vec![
+                    Segment::Key("properties".to_string()),
+                    Segment::Key("key".to_string()),
+                ],
+                json!("abc==")
+            )
+        );
+        assert_eq!(
+            Edit::parse(ADD_OPTION, &["tags", "k=v"], None).unwrap(),
+            Edit::Add(vec![Segment::Key("tags".to_string())], json!("k=v"))
+        );
+        assert_eq!(
+            Edit::parse(REMOVE_OPTION, &["a"], None).unwrap(),
+            Edit::Remove(vec![Segment::Key("a".to_string())])
+        );
+        assert!(Edit::parse(SET_OPTION, &["a"], None).is_err());
+        assert!(Edit::parse(ADD_OPTION, &["tags"], None).is_err());
+        assert!(Edit::parse(REMOVE_OPTION, &["a..b"], None).is_err());
+        assert!(Edit::parse(REMOVE_OPTION, &["a[x]"], None).is_err());
+        assert!(Edit::parse(REMOVE_OPTION, &["a[0"], None).is_err());
+    }
+
+    #[test]
+    fn parse_edit_with_schema() {
+        let schema: Schema = serde_json::from_value(json!({
+            "type": "object",
+            "props": [
+                {
+                    "type": "object",
+                    "name": "properties",
+                    "props": [
+                        {"type": "integer32", "name": "count"},
+                        {"type": "string", "name": "version"},
+                        {"type": "array<string>", "name": "zones", "item": {"type": "string"}}
+                    ]
+                },
+                {"type": "object", "name": "tags", "additionalProps": {"item": {"type": "string"}}}
+            ]
+        }))
+        .unwrap();
+        let parse = |option, values: &[&str]| Edit::parse(option, values, Some(&schema)).unwrap();
+        let key = |k: &str| Segment::Key(k.to_string());
+
+        // The numeric looking strings are kept as strings.
+        assert_eq!(
+            parse(SET_OPTION, &["tags.env=1"]),
+            Edit::Set(vec![key("tags"), key("env")], json!("1"))
+        );
+        assert_eq!(
+            parse(SET_OPTION, &["properties.version=1.0"]),
+            Edit::Set(vec![key("properties"), key("version")], json!("1.0"))
+        );
+        assert_eq!(
+            parse(SET_OPTION, &["properties.count=1"]),
+            Edit::Set(vec![key("properties"), key("count")], json!(1))
+        );
+        assert_eq!(
+            parse(SET_OPTION, &["properties={count:2,version:2}"]),
+            Edit::Set(vec![key("properties")], json!({"count": 2, "version": "2"}))
+        );
+        assert_eq!(
+            parse(ADD_OPTION, &["properties.zones", "1"]),
+            Edit::Add(vec![key("properties"), key("zones")], json!("1"))
+        );
+        assert_eq!(
+            parse(ADD_OPTION, &["tags", "k=1"]),
+            Edit::Add(vec![key("tags")], json!({"k": "1"}))
+        );
+        assert!(Edit::parse(SET_OPTION, &["properties.count=x"], Some(&schema)).is_err());
+
+        // The paths not defined by the schema are parsed as is.
+        assert_eq!(
+            parse(SET_OPTION, &["properties.other=1"]),
+            Edit::Set(vec![key("properties"), key("other")], json!(1))
+        );
+    }
+
+    #[test]
+    fn apply_edits() {
+        let mut body = json!({
+            "properties": {"list": [1, 2], "y": "y"},
+            "tags": {"a": "b"},
+        });
+        let edits: [(&str, &[&str]); 9] = [
+            (SET_OPTION, &["properties.x=1"]),
+            (SET_OPTION, &["properties.sub.name=foo"]),
+            (ADD_OPTION, &["tags", "k=v"]),
+            (ADD_OPTION, &["properties.list", "3"]),
+            (ADD_OPTION, &["properties.list", "abc=="]),
+            (ADD_OPTION, &["properties.zones", "1"]),
+            (ADD_OPTION, &["properties.labels", "k=v"]),
+            (REMOVE_OPTION, &["properties.y"]),
+            (REMOVE_OPTION, &["properties.list[0]"]),
+        ]
+        .into_iter()
+        .map(|(option, values)| Edit::parse(option, values, None).unwrap())
+        .collect::<Vec<_>>();
+        apply(&mut body, &edits).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "properties": {
+                    "list": [2, 3, "abc=="],
+                    "x": 1,
+                    "sub": {"name": "foo"},
+                    "zones": [1],
+                    "labels": {"k": "v"},
+                },
+                "tags": {"a": "b", "k": "v"},
+            })
+        );
+
+        let failures: [(&str, &[&str]); 4] = [
+            (REMOVE_OPTION, &["properties.z"]),
+            (REMOVE_OPTION, &["properties.list[5]"]),
+            (SET_OPTION, &["properties.x.y=1"]),
+            (ADD_OPTION, &["tags", "1"]),
+        ];
+        for (option, values) in failures {
+            let edit = Edit::parse(option, values, None).unwrap();
+            assert!(edit.apply(&mut body).is_err(), "{values:?}");
+        }
+    }
+
+    #[test]
+    fn add_to_absent() {
+        // Without the schema, the absent path is created by the added value: the key/value pairs
+        // as an object, and the others as an array.
+        for (path, value, expect) in [
+            ("tags", "k=v", json!({"tags": {"k": "v"}})),
+            ("tags", "k=v k2=v2", json!({"tags": {"k": "v", "k2": "v2"}})),
+            ("zones", "1", json!({"zones": [1]})),
+            ("zones", "a", json!({"zones": ["a"]})),
+        ] {
+            let mut body = json!({});
+            Edit::parse(ADD_OPTION, &[path, value], None)
+                .unwrap()
+                .apply(&mut body)
+                .unwrap();
+            assert_eq!(body, expect, "{value}");
+        }
+    }
+
+    #[test]
+    fn merge_values() {
+        let mut body = json!({"a": {"b": 1, "c": [1]}, "d": 1});
+        merge(&mut body, json!({"a": {"c": [2], "e": 1}, "f": 1}));
+        assert_eq!(
+            body,
+            json!({"a": {"b": 1, "c": [2], "e": 1}, "d": 1, "f": 1})
+        );
+    }
+
+    #[test]
+    fn edits_from_matches() {
+        let matches = clap::Command::new("test")
+            .arg(
+                clap::Arg::new(SET_OPTION)
+                    .long(SET_OPTION)
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                clap::Arg::new(ADD_OPTION)
+                    .long(ADD_OPTION)
+                    .num_args(2)
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                clap::Arg::new(REMOVE_OPTION)
+                    .long(REMOVE_OPTION)
+                    .action(clap::ArgAction::Append),
+            )
+            .get_matches_from(vec![
+                "test", "--add", "tags", "k=v", "--set", "tags.k=x", "--add", "tags", "a=b=c",
+                "--remove", "tags.k",
+            ]);
+        let key = |k: &str| Segment::Key(k.to_string());
+        assert_eq!(
+            Edit::from_matches(&matches, None).unwrap(),
+            vec![
+                Edit::Add(vec![key("tags")], json!("k=v")),
+                Edit::Set(vec![key("tags"), key("k")], json!("x")),
+                Edit::Add(vec![key("tags")], json!("a=b=c")),
+                Edit::Remove(vec![key("tags"), key("k")]),
+            ]
+        );
+    }
+}
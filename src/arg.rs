@@ -60,7 +60,8 @@ impl CliInput {
                     // Handle --key [value] or just --flag
                     let key = arg.to_string();
                     match args.peek() {
-                        Some(next) if !next.as_ref().starts_with("-") => {
+                        // The "-" value usually means stdin, e.g. "-f -".
+                        Some(next) if next.as_ref() == "-" || !next.as_ref().starts_with("-") => {
                             let value = args.next().map(|v| String::from(v.as_ref()));
                             result.push(Arg::Optional(key, value));
                         }
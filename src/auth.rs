@@ -0,0 +1,476 @@
+use std::{env, fmt::Display, path::PathBuf, str::FromStr};
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+pub const AUTH_MODE_ENV: &str = "AZURE_AUTH_MODE";
+pub const TENANT_ID_ENV: &str = "AZURE_TENANT_ID";
+pub const CLIENT_ID_ENV: &str = "AZURE_CLIENT_ID";
+pub const CLIENT_SECRET_ENV: &str = "AZURE_CLIENT_SECRET";
+pub const CLIENT_CERTIFICATE_PATH_ENV: &str = "AZURE_CLIENT_CERTIFICATE_PATH";
+pub const CLIENT_CERTIFICATE_PASSWORD_ENV: &str = "AZURE_CLIENT_CERTIFICATE_PASSWORD";
+pub const FEDERATED_TOKEN_FILE_ENV: &str = "AZURE_FEDERATED_TOKEN_FILE";
+pub const TOKEN_FILE_ENV: &str = "AZURE_TOKEN_FILE";
+
+// ARM accepts the tokens of at most 3 auxiliary tenants.
+pub const MAX_AUX_TENANTS: usize = 3;
+
+// The public client used by the device code flow by default, i.e. the one of the Azure CLI.
+pub const DEFAULT_PUBLIC_CLIENT_ID: &str = "04b07795-8ddb-461a-bbee-02f9e1bf7b46";
+
+// AuthMode selects the credential to authenticate with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuthMode {
+    // The DefaultAzureCredential of the Azure Identity SDK
+    #[default]
+    Default,
+    // The signed in account of the Azure CLI
+    AzureCli,
+    ClientSecret,
+    ClientCertificate,
+    // The federated token file, e.g. of the workload identity of AKS, or the OIDC of the CI
+    WorkloadIdentity,
+    ManagedIdentity,
+    DeviceCode,
+    // A file containing an access token, which is read as is
+    TokenFile,
+}
+
+impl AuthMode {
+    pub fn all() -> [Self; 8] {
+        [
+            Self::Default,
+            Self::AzureCli,
+            Self::ClientSecret,
+            Self::ClientCertificate,
+            Self::WorkloadIdentity,
+            Self::ManagedIdentity,
+            Self::DeviceCode,
+            Self::TokenFile,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::AzureCli => "azure-cli",
+            Self::ClientSecret => "client-secret",
+            Self::ClientCertificate => "client-certificate",
+            Self::WorkloadIdentity => "workload-identity",
+            Self::ManagedIdentity => "managed-identity",
+            Self::DeviceCode => "device-code",
+            Self::TokenFile => "token-file",
+        }
+    }
+}
+
+impl Display for AuthMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for AuthMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all()
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s))
+            .ok_or(anyhow!(
+                r#"unknown auth mode "{s}", expect one of {}"#,
+                Self::all().map(|mode| mode.name()).join(", ")
+            ))
+    }
+}
+
+// AuthSpec is the authentication settings in the config file. The secrets (i.e. the client secret
+// and the certificate password) are only read from the environment variables.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct AuthSpec {
+    pub mode: Option<String>,
+    pub tenant_id: Option<String>,
+    pub client_id: Option<String>,
+    // The path of the PKCS#12 (.pfx) certificate.
+    pub client_certificate: Option<String>,
+    pub federated_token_file: Option<String>,
+    pub token_file: Option<String>,
+    // The comma separated auxiliary tenants.
+    pub aux_tenants: Option<String>,
+}
+
+impl AuthSpec {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// Auth is the resolved authentication settings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Auth {
+    pub mode: AuthMode,
+    pub tenant_id: Option<String>,
+    pub client_id: Option<String>,
+    pub client_certificate: Option<PathBuf>,
+    pub federated_token_file: Option<PathBuf>,
+    pub token_file: Option<PathBuf>,
+    // The tenants whose tokens are sent along with the one of the main tenant, for the cross
+    // tenant operations.
+    pub aux_tenants: Vec<String>,
+}
+
+impl Auth {
+    // resolve resolves the auth mode from (in order):
+    // 1. The "--auth-mode" option
+    // 2. The AZURE_AUTH_MODE environment variable
+    // 3. The "auth.mode" in the config file
+    // The mode defaults to "default". The other settings are read from the config file, and can be
+    // overridden by the environment variables, e.g. AZURE_TENANT_ID, AZURE_CLIENT_ID.
+    // The auxiliary tenants are from the "--aux-tenants" option, or the "auth.aux_tenants" in the
+    // config file.
+    pub fn resolve(mode: Option<&str>, aux_tenants: &[String], config: &Config) -> Result<Self> {
+        let spec = &config.auth;
+        let env_mode = env::var(AUTH_MODE_ENV).ok();
+        let mode = match mode.or(env_mode.as_deref()).or(spec.mode.as_deref()) {
+            Some(mode) => mode.parse()?,
+            None => AuthMode::Default,
+        };
+        let setting =
+            |env_name: &str, value: &Option<String>| env::var(env_name).ok().or(value.clone());
+        let auth = Self {
+            mode,
+            tenant_id: setting(TENANT_ID_ENV, &spec.tenant_id),
+            client_id: setting(CLIENT_ID_ENV, &spec.client_id),
+            client_certificate: setting(CLIENT_CERTIFICATE_PATH_ENV, &spec.client_certificate)
+                .map(PathBuf::from),
+            federated_token_file: setting(FEDERATED_TOKEN_FILE_ENV, &spec.federated_token_file)
+                .map(PathBuf::from),
+            token_file: setting(TOKEN_FILE_ENV, &spec.token_file).map(PathBuf::from),
+            aux_tenants: if aux_tenants.is_empty() {
+                spec.aux_tenants
+                    .iter()
+                    .flat_map(|v| v.split(','))
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect()
+            } else {
+                aux_tenants.to_vec()
+            },
+        };
+        if auth.aux_tenants.len() > MAX_AUX_TENANTS {
+            bail!("at most {MAX_AUX_TENANTS} auxiliary tenants are allowed");
+        }
+        // The principal of the default credential is only known once it is resolved, so the tokens
+        // of the auxiliary tenants can't be guaranteed to be issued to the same one.
+        if !auth.aux_tenants.is_empty() && auth.mode == AuthMode::Default {
+            bail!(
+                r#"the auxiliary tenants require an explicit auth mode (e.g. "--{} azure-cli"), rather than "{}""#,
+                crate::cmd::AUTH_MODE_OPTION,
+                auth.mode
+            );
+        }
+        if !auth.aux_tenants.is_empty()
+            && [AuthMode::ManagedIdentity, AuthMode::TokenFile].contains(&auth.mode)
+        {
+            bail!(
+                r#"the auth mode "{}" doesn't support the auxiliary tenants"#,
+                auth.mode
+            );
+        }
+        Ok(auth)
+    }
+
+    // for_aux_tenant returns the settings to authenticate against the auxiliary tenant, with the
+    // same auth mode and client.
+    pub fn for_aux_tenant(&self, tenant_id: &str) -> Self {
+        Self {
+            tenant_id: Some(tenant_id.to_string()),
+            aux_tenants: vec![],
+            ..self.clone()
+        }
+    }
+
+    // is_principal_pinned tells whether the principal the tokens are issued to is determined by
+    // the settings, or by the refresh token of the user signing in by the device code (which is
+    // cached per tenant and client). Otherwise (e.g. the signed in account of the Azure CLI, or
+    // the system assigned managed identity), the tokens of different principals would share the
+    // same cache key.
+    pub fn is_principal_pinned(&self) -> bool {
+        match self.mode {
+            AuthMode::ClientSecret | AuthMode::ClientCertificate | AuthMode::WorkloadIdentity => {
+                self.tenant_id.is_some() && self.client_id.is_some()
+            }
+            AuthMode::ManagedIdentity => self.client_id.is_some(),
+            AuthMode::DeviceCode => true,
+            AuthMode::Default | AuthMode::AzureCli | AuthMode::TokenFile => false,
+        }
+    }
+
+    // cache_key returns the key of the cached tokens for the authority host and scopes.
+    pub fn cache_key(&self, authority_host: &str, scopes: &[&str]) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.mode,
+            authority_host.trim_end_matches('/'),
+            self.tenant_id.as_deref().unwrap_or_default(),
+            self.client_id.as_deref().unwrap_or_default(),
+            scopes.join(" ")
+        )
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Auth {
+    // credential builds the credential of the auth mode. The tokens of the pinned principal are
+    // cached on disk, so that the consecutive invocations don't each acquire a new token.
+    pub fn credential(
+        &self,
+        cloud: &crate::cloud::Cloud,
+    ) -> Result<std::sync::Arc<dyn azure_core::credentials::TokenCredential>> {
+        use anyhow::Context;
+        use azure_core::credentials::TokenCredential;
+        use azure_identity::{
+            AzureCliCredential, AzureCliCredentialOptions, ClientAssertionCredentialOptions,
+            ClientCertificateCredential, ClientCertificateCredentialOptions,
+            ClientSecretCredential, ClientSecretCredentialOptions, DefaultAzureCredential,
+            ManagedIdentityCredential, ManagedIdentityCredentialOptions, UserAssignedId,
+            WorkloadIdentityCredential, WorkloadIdentityCredentialOptions,
+        };
+        use base64::Engine;
+        use std::sync::Arc;
+
+        use crate::azidentityext::{
+            access_token_credential::AccessTokenCredential,
+            device_code_credential::DeviceCodeCredential, token_cache::CachedTokenCredential,
+        };
+
+        let required = |value: &Option<String>, env_name: &str| {
+            value.clone().ok_or(anyhow!(
+                r#"the auth mode "{}" requires the {env_name} environment variable or the config"#,
+                self.mode
+            ))
+        };
+
+        let cred: Arc<dyn TokenCredential> = match self.mode {
+            AuthMode::Default => DefaultAzureCredential::new()?,
+            AuthMode::AzureCli => AzureCliCredential::new(Some(AzureCliCredentialOptions {
+                tenant_id: self.tenant_id.clone(),
+                ..Default::default()
+            }))?,
+            AuthMode::ClientSecret => {
+                let secret = env::var(CLIENT_SECRET_ENV).map_err(|_| {
+                    anyhow!(r#"the auth mode "{}" requires the {CLIENT_SECRET_ENV} environment variable"#, self.mode)
+                })?;
+                ClientSecretCredential::new(
+                    &required(&self.tenant_id, TENANT_ID_ENV)?,
+                    required(&self.client_id, CLIENT_ID_ENV)?,
+                    secret.into(),
+                    Some(ClientSecretCredentialOptions {
+                        credential_options: credential_options(cloud),
+                    }),
+                )?
+            }
+            AuthMode::ClientCertificate => {
+                let path = self.client_certificate.as_ref().ok_or(anyhow!(
+                    r#"the auth mode "{}" requires the {CLIENT_CERTIFICATE_PATH_ENV} environment variable or the config"#,
+                    self.mode
+                ))?;
+                let certificate = std::fs::read(path)
+                    .context(format!("reading the client certificate {path:?}"))?;
+                let password = env::var(CLIENT_CERTIFICATE_PASSWORD_ENV).unwrap_or_default();
+                ClientCertificateCredential::new(
+                    required(&self.tenant_id, TENANT_ID_ENV)?,
+                    required(&self.client_id, CLIENT_ID_ENV)?,
+                    base64::engine::general_purpose::STANDARD.encode(certificate),
+                    password,
+                    Some(ClientCertificateCredentialOptions {
+                        credential_options: credential_options(cloud),
+                        ..Default::default()
+                    }),
+                )?
+            }
+            AuthMode::WorkloadIdentity => {
+                WorkloadIdentityCredential::new(Some(WorkloadIdentityCredentialOptions {
+                    credential_options: ClientAssertionCredentialOptions {
+                        credential_options: credential_options(cloud),
+                        ..Default::default()
+                    },
+                    tenant_id: self.tenant_id.clone(),
+                    client_id: self.client_id.clone(),
+                    token_file_path: self.federated_token_file.clone(),
+                    ..Default::default()
+                }))?
+            }
+            AuthMode::ManagedIdentity => {
+                ManagedIdentityCredential::new(Some(ManagedIdentityCredentialOptions {
+                    user_assigned_id: self.client_id.clone().map(UserAssignedId::ClientId),
+                    ..Default::default()
+                }))?
+            }
+            AuthMode::DeviceCode => DeviceCodeCredential::new(
+                &cloud.authority_host,
+                self.tenant_id.as_deref().unwrap_or("organizations"),
+                self.client_id
+                    .as_deref()
+                    .unwrap_or(DEFAULT_PUBLIC_CLIENT_ID),
+            )?,
+            AuthMode::TokenFile => {
+                let path = self.token_file.as_ref().ok_or(anyhow!(
+                    r#"the auth mode "{}" requires the {TOKEN_FILE_ENV} environment variable or the config"#,
+                    self.mode
+                ))?;
+                let token = std::fs::read_to_string(path)
+                    .context(format!("reading the token file {path:?}"))?;
+                return Ok(AccessTokenCredential::new(token.trim().to_string())?);
+            }
+        };
+        if !self.is_principal_pinned() {
+            return Ok(cred);
+        }
+        Ok(CachedTokenCredential::new(
+            cred,
+            self.clone(),
+            cloud.authority_host.clone(),
+        ))
+    }
+}
+
+// credential_options returns the options of the credentials that acquire the tokens from Microsoft
+// Entra by themselves, which sign in to the authority host of the cloud. The Azure CLI (also
+// chained by the default credential) signs in to its own configured cloud, and the managed identity
+// is served by the local endpoint.
+#[cfg(not(target_arch = "wasm32"))]
+fn credential_options(cloud: &crate::cloud::Cloud) -> azure_identity::TokenCredentialOptions {
+    let mut options = azure_identity::TokenCredentialOptions::default();
+    options.set_authority_host(cloud.authority_host.clone());
+    options
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_mode() {
+        for mode in AuthMode::all() {
+            assert_eq!(mode.name().parse::<AuthMode>().unwrap(), mode);
+        }
+        assert_eq!(
+            "Device-Code".parse::<AuthMode>().unwrap(),
+            AuthMode::DeviceCode
+        );
+        assert!("foo".parse::<AuthMode>().is_err());
+    }
+
+    #[test]
+    fn resolve() {
+        let config = Config {
+            auth: AuthSpec {
+                mode: Some("managed-identity".to_string()),
+                client_id: Some("client".to_string()),
+                ..AuthSpec::default()
+            },
+            ..Config::default()
+        };
+        let auth = Auth::resolve(Some("workload-identity"), &[], &config).unwrap();
+        assert_eq!(auth.mode, AuthMode::WorkloadIdentity);
+        assert!(Auth::resolve(Some("foo"), &[], &config).is_err());
+        assert_eq!(
+            Auth {
+                mode: AuthMode::ManagedIdentity,
+                client_id: Some("client".to_string()),
+                ..Auth::default()
+            }
+            .cache_key(
+                "https://login.microsoftonline.com/",
+                &["https://management.azure.com/.default"]
+            ),
+            "managed-identity|https://login.microsoftonline.com||client|https://management.azure.com/.default"
+        );
+    }
+
+    #[test]
+    fn aux_tenants() {
+        let mut config = Config {
+            auth: AuthSpec {
+                aux_tenants: Some("t1, t2".to_string()),
+                ..AuthSpec::default()
+            },
+            ..Config::default()
+        };
+        let auth = Auth::resolve(Some("azure-cli"), &[], &config).unwrap();
+        assert_eq!(auth.aux_tenants, vec!["t1", "t2"]);
+        assert_eq!(
+            auth.for_aux_tenant("t1"),
+            Auth {
+                tenant_id: Some("t1".to_string()),
+                aux_tenants: vec![],
+                ..auth.clone()
+            }
+        );
+
+        // The option takes precedence over the config.
+        let auth = Auth::resolve(Some("azure-cli"), &["t3".to_string()], &config).unwrap();
+        assert_eq!(auth.aux_tenants, vec!["t3"]);
+
+        config.auth.aux_tenants = Some("t1,t2,t3,t4".to_string());
+        assert!(Auth::resolve(Some("azure-cli"), &[], &config).is_err());
+        config.auth.aux_tenants = Some("t1".to_string());
+        assert!(Auth::resolve(Some("managed-identity"), &[], &config).is_err());
+        // The principal of the default credential is not known up front.
+        assert!(Auth::resolve(Some("default"), &[], &config).is_err());
+    }
+
+    #[test]
+    fn principal_pinned() {
+        let mut auth = Auth {
+            mode: AuthMode::ManagedIdentity,
+            ..Auth::default()
+        };
+        assert!(!auth.is_principal_pinned());
+        auth.client_id = Some("client".to_string());
+        assert!(auth.is_principal_pinned());
+        auth.mode = AuthMode::ClientSecret;
+        assert!(!auth.is_principal_pinned());
+        auth.tenant_id = Some("tenant".to_string());
+        assert!(auth.is_principal_pinned());
+        for mode in [AuthMode::Default, AuthMode::AzureCli] {
+            auth.mode = mode;
+            assert!(!auth.is_principal_pinned());
+        }
+        auth.mode = AuthMode::DeviceCode;
+        auth.tenant_id = None;
+        assert!(auth.is_principal_pinned());
+    }
+
+    #[test]
+    fn sovereign_cloud() {
+        // The credentials sign in to the authority host of the cloud, for the scope of its ARM
+        // audience, and their tokens are cached by both.
+        let cloud = crate::cloud::Cloud::azure_china_cloud();
+        assert_eq!(
+            credential_options(&cloud)
+                .authority_host()
+                .unwrap()
+                .as_str(),
+            "https://login.chinacloudapi.cn/"
+        );
+        assert_eq!(
+            cloud.scope(),
+            "https://management.chinacloudapi.cn/.default"
+        );
+        let auth = Auth {
+            mode: AuthMode::ClientSecret,
+            tenant_id: Some("tenant".to_string()),
+            client_id: Some("client".to_string()),
+            ..Auth::default()
+        };
+        assert_eq!(
+            auth.cache_key(&cloud.authority_host, &[&cloud.scope()]),
+            "client-secret|https://login.chinacloudapi.cn|tenant|client|https://management.chinacloudapi.cn/.default"
+        );
+    }
+}
@@ -1 +1,5 @@
 pub mod access_token_credential;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod device_code_credential;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod token_cache;
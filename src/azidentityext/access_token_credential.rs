@@ -1,22 +1,45 @@
 use azure_core::credentials::TokenRequestOptions;
 use azure_core::credentials::{AccessToken, TokenCredential};
+use azure_core::error::ErrorKind;
 use azure_core::time::{Duration, OffsetDateTime};
-use azure_core::Result;
+use azure_core::{Error, Result};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use std::fmt::Debug;
+use std::sync::Mutex;
 use std::{str, sync::Arc};
 
+// The token is refreshed a while ahead of its expiry, so that it doesn't expire in the middle of
+// a request.
+const REFRESH_MARGIN_SECONDS: i64 = 300;
+
+/// Provides a new access token when the current one is about to expire, e.g. by calling back to
+/// the host of the WASM module.
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+pub trait TokenRefresher: Debug + Send + Sync {
+    async fn refresh(&self) -> Result<String>;
+}
+
 /// Authenticates an application with an existing access token.
 #[derive(Debug)]
 pub struct AccessTokenCredential {
-    token: AccessToken,
+    token: Mutex<AccessToken>,
+    refresher: Option<Arc<dyn TokenRefresher>>,
 }
 
 impl AccessTokenCredential {
     pub fn new(token: String) -> Result<Arc<Self>> {
         Ok(Arc::new(Self {
-            token: AccessToken {
-                token: token.into(),
-                expires_on: OffsetDateTime::now_utc() + Duration::hours(1),
-            },
+            token: Mutex::new(access_token(token)),
+            refresher: None,
+        }))
+    }
+
+    /// Creates the credential whose token is refreshed by the refresher when it is about to expire.
+    pub fn with_refresher(token: String, refresher: Arc<dyn TokenRefresher>) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self {
+            token: Mutex::new(access_token(token)),
+            refresher: Some(refresher),
         }))
     }
 }
@@ -25,6 +48,109 @@ impl AccessTokenCredential {
 #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
 impl TokenCredential for AccessTokenCredential {
     async fn get_token(&self, _: &[&str], _: Option<TokenRequestOptions>) -> Result<AccessToken> {
-        Ok(self.token.clone())
+        let token = self.token.lock().unwrap().clone();
+        let Some(refresher) = &self.refresher else {
+            return Ok(token);
+        };
+        if token.expires_on - Duration::seconds(REFRESH_MARGIN_SECONDS) > OffsetDateTime::now_utc()
+        {
+            return Ok(token);
+        }
+        let token =
+            access_token(refresher.refresh().await.map_err(|e| {
+                Error::full(ErrorKind::Credential, e, "refreshing the access token")
+            })?);
+        *self.token.lock().unwrap() = token.clone();
+        Ok(token)
+    }
+}
+
+// access_token builds the access token, whose expiry is read from the "exp" claim if the token is
+// a JWT, or one hour later otherwise.
+fn access_token(token: String) -> AccessToken {
+    let expires_on = jwt_expiry(&token).unwrap_or(OffsetDateTime::now_utc() + Duration::hours(1));
+    AccessToken {
+        token: token.into(),
+        expires_on,
+    }
+}
+
+// jwt_expiry decodes the "exp" claim from the payload of the JWT, without verifying its signature.
+fn jwt_expiry(token: &str) -> Option<OffsetDateTime> {
+    let payload = token.split('.').nth(1)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    OffsetDateTime::from_unix_timestamp(claims.get("exp")?.as_i64()?).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn jwt(exp: i64) -> String {
+        let encode = |v: serde_json::Value| URL_SAFE_NO_PAD.encode(v.to_string());
+        format!(
+            "{}.{}.signature",
+            encode(serde_json::json!({"alg": "RS256", "typ": "JWT"})),
+            encode(serde_json::json!({"aud": "https://management.azure.com", "exp": exp})),
+        )
+    }
+
+    #[derive(Debug, Default)]
+    struct Refresher {
+        count: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenRefresher for Refresher {
+        async fn refresh(&self) -> Result<String> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(jwt(OffsetDateTime::now_utc().unix_timestamp() + 3600))
+        }
+    }
+
+    #[test]
+    fn expiry() {
+        assert_eq!(
+            jwt_expiry(&jwt(1700000000)),
+            Some(OffsetDateTime::from_unix_timestamp(1700000000).unwrap())
+        );
+        assert_eq!(jwt_expiry("opaque"), None);
+        assert_eq!(jwt_expiry("a.b.c"), None);
+    }
+
+    #[tokio::test]
+    async fn refresh() {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let refresher = Arc::new(Refresher::default());
+
+        // The token far from expiry is not refreshed.
+        let cred =
+            AccessTokenCredential::with_refresher(jwt(now + 3600), refresher.clone()).unwrap();
+        assert_eq!(
+            cred.get_token(&[], None).await.unwrap().token.secret(),
+            jwt(now + 3600)
+        );
+        assert_eq!(refresher.count.load(Ordering::SeqCst), 0);
+
+        // The token about to expire is refreshed once.
+        let cred = AccessTokenCredential::with_refresher(jwt(now + 60), refresher.clone()).unwrap();
+        let token = cred.get_token(&[], None).await.unwrap();
+        assert!(token.expires_on.unix_timestamp() >= now + 3600);
+        cred.get_token(&[], None).await.unwrap();
+        assert_eq!(refresher.count.load(Ordering::SeqCst), 1);
+
+        // The token without a refresher is returned as is.
+        let cred = AccessTokenCredential::new(jwt(now - 60)).unwrap();
+        assert_eq!(
+            cred.get_token(&[], None)
+                .await
+                .unwrap()
+                .expires_on
+                .unix_timestamp(),
+            now - 60
+        );
     }
 }
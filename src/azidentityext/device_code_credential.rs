@@ -0,0 +1,499 @@
+use std::sync::Arc;
+
+use azure_core::credentials::{AccessToken, TokenCredential, TokenRequestOptions};
+use azure_core::error::ErrorKind;
+use azure_core::http::policies::Policy;
+use azure_core::http::{ClientOptions, Context, Method, Pipeline, Request, Url};
+use azure_core::time::{Duration, OffsetDateTime};
+use azure_core::{Error, Result};
+use bytes::Bytes;
+use serde::{Deserialize, de::DeserializeOwned};
+use typespec_client_core::sleep::sleep;
+
+use super::token_cache::TokenCache;
+use crate::client::response::Response;
+
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+const REFRESH_TOKEN_GRANT_TYPE: &str = "refresh_token";
+
+// The scope that asks for the refresh token, along with the access token.
+const OFFLINE_ACCESS_SCOPE: &str = "offline_access";
+
+// The refresh tokens of the public clients are valid for 90 days, unless they are revoked.
+const REFRESH_TOKEN_LIFETIME: Duration = Duration::days(90);
+
+// The polling interval is increased by 5 seconds on "slow_down", as RFC 8628 requires.
+#[cfg(not(test))]
+const SLOW_DOWN_INCREMENT: Duration = Duration::seconds(5);
+#[cfg(test)]
+const SLOW_DOWN_INCREMENT: Duration = Duration::milliseconds(10);
+
+#[derive(Debug, Deserialize)]
+struct DeviceCodeResponse {
+    device_code: Option<String>,
+    message: Option<String>,
+    expires_in: Option<i64>,
+    interval: Option<i64>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Authenticates a user by the OAuth 2.0 device code flow, where the user signs in on another
+/// device with the code printed to the stderr.
+///
+/// The refresh token of the signed in user is kept in the token cache, keyed by the authority
+/// host, tenant and client, so that the user only signs in again once it is expired or revoked.
+///
+/// The flow is implemented here as azure_identity provides no device code credential.
+#[derive(Debug)]
+pub struct DeviceCodeCredential {
+    // The OAuth 2.0 endpoint of the tenant, e.g. "https://login.microsoftonline.com/organizations/oauth2/v2.0/"
+    endpoint: Url,
+    client_id: String,
+    pipeline: Pipeline,
+    cache: Option<TokenCache>,
+    cache_key: String,
+}
+
+impl DeviceCodeCredential {
+    pub fn new(authority_host: &str, tenant_id: &str, client_id: &str) -> Result<Arc<Self>> {
+        let cache = TokenCache::from_config_dir();
+        Ok(Arc::new(Self::with_policies(
+            authority_host,
+            tenant_id,
+            client_id,
+            cache,
+            vec![],
+        )?))
+    }
+
+    fn with_policies(
+        authority_host: &str,
+        tenant_id: &str,
+        client_id: &str,
+        cache: Option<TokenCache>,
+        policies: Vec<Arc<dyn Policy>>,
+    ) -> Result<Self> {
+        let authority_host = authority_host.trim_end_matches('/');
+        let endpoint = Url::parse(&format!("{authority_host}/{tenant_id}/oauth2/v2.0/"))?;
+        let pipeline = Pipeline::new(
+            option_env!("CARGO_PKG_NAME"),
+            option_env!("CARGO_PKG_VERSION"),
+            ClientOptions::default(),
+            vec![],
+            policies,
+        );
+        Ok(Self {
+            endpoint,
+            client_id: client_id.to_string(),
+            pipeline,
+            cache,
+            cache_key: format!(
+                "{REFRESH_TOKEN_GRANT_TYPE}|{authority_host}|{tenant_id}|{client_id}"
+            ),
+        })
+    }
+
+    async fn post<T: DeserializeOwned>(&self, path: &str, form: &[(&str, &str)]) -> Result<T> {
+        let mut request = Request::new(self.endpoint.join(path)?, Method::Post);
+        request.insert_header("accept", "application/json");
+        request.insert_header("content-type", "application/x-www-form-urlencoded");
+        request.set_body(Bytes::from(form_urlencoded(form)));
+        let resp = self.pipeline.send(&Context::new(), &mut request).await?;
+        let resp = Response::from_raw_response(resp).await?;
+        serde_json::from_slice(&resp.body).map_err(|e| Error::new(ErrorKind::DataConversion, e))
+    }
+
+    // refresh redeems the cached refresh token for the access token, if there is one.
+    async fn refresh(&self, scope: &str) -> Result<Option<AccessToken>> {
+        let Some(cache) = &self.cache else {
+            return Ok(None);
+        };
+        let refresh_token = match cache.get(&self.cache_key) {
+            Ok(Some(token)) => token.token.secret().to_string(),
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                tracing::warn!("reading the token cache: {e:#}");
+                return Ok(None);
+            }
+        };
+        let resp: TokenResponse = self
+            .post(
+                "token",
+                &[
+                    ("grant_type", REFRESH_TOKEN_GRANT_TYPE),
+                    ("client_id", &self.client_id),
+                    ("refresh_token", &refresh_token),
+                    ("scope", scope),
+                ],
+            )
+            .await?;
+        if let Some(error) = &resp.error {
+            return Err(Error::message(
+                ErrorKind::Credential,
+                format!(
+                    "refreshing the token: {error}: {}",
+                    resp.error_description.unwrap_or_default()
+                ),
+            ));
+        }
+        self.issue(resp).map(Some)
+    }
+
+    // issue returns the access token of the successful token response, and caches its refresh
+    // token (which is renewed by each refresh) for the subsequent invocations.
+    fn issue(&self, resp: TokenResponse) -> Result<AccessToken> {
+        let Some(access_token) = resp.access_token else {
+            return Err(Error::message(
+                ErrorKind::Credential,
+                "the token response has neither the access token nor the error",
+            ));
+        };
+        if let (Some(cache), Some(refresh_token)) = (&self.cache, resp.refresh_token) {
+            let refresh_token = AccessToken {
+                token: refresh_token.into(),
+                expires_on: OffsetDateTime::now_utc() + REFRESH_TOKEN_LIFETIME,
+            };
+            if let Err(e) = cache.set(&self.cache_key, &refresh_token) {
+                tracing::warn!("writing the token cache: {e:#}");
+            }
+        }
+        Ok(AccessToken {
+            token: access_token.into(),
+            expires_on: OffsetDateTime::now_utc()
+                + Duration::seconds(resp.expires_in.unwrap_or(3600)),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenCredential for DeviceCodeCredential {
+    async fn get_token(
+        &self,
+        scopes: &[&str],
+        _: Option<TokenRequestOptions>,
+    ) -> Result<AccessToken> {
+        let mut scopes = scopes.to_vec();
+        scopes.push(OFFLINE_ACCESS_SCOPE);
+        let scope = scopes.join(" ");
+        match self.refresh(&scope).await {
+            Ok(Some(token)) => return Ok(token),
+            Ok(None) => {}
+            // The refresh token is e.g. revoked, then the user signs in again.
+            Err(e) => tracing::warn!("{e}"),
+        }
+
+        let code: DeviceCodeResponse = self
+            .post(
+                "devicecode",
+                &[("client_id", &self.client_id), ("scope", &scope)],
+            )
+            .await?;
+        let Some(device_code) = code.device_code else {
+            return Err(Error::message(
+                ErrorKind::Credential,
+                format!(
+                    "requesting the device code: {}: {}",
+                    code.error.unwrap_or_default(),
+                    code.error_description.unwrap_or_default()
+                ),
+            ));
+        };
+        if let Some(message) = &code.message {
+            eprintln!("{message}");
+        }
+
+        let deadline =
+            OffsetDateTime::now_utc() + Duration::seconds(code.expires_in.unwrap_or(900));
+        let mut interval = Duration::seconds(code.interval.unwrap_or(5));
+        while OffsetDateTime::now_utc() < deadline {
+            sleep(interval).await;
+            let resp: TokenResponse = self
+                .post(
+                    "token",
+                    &[
+                        ("grant_type", DEVICE_CODE_GRANT_TYPE),
+                        ("client_id", &self.client_id),
+                        ("device_code", &device_code),
+                    ],
+                )
+                .await?;
+            match resp.error.as_deref() {
+                None => return self.issue(resp),
+                // The user hasn't finished the sign in yet.
+                Some("authorization_pending") => {}
+                Some("slow_down") => interval += SLOW_DOWN_INCREMENT,
+                Some(error) => {
+                    return Err(Error::message(
+                        ErrorKind::Credential,
+                        format!(
+                            "authenticating by the device code: {error}: {}",
+                            resp.error_description.unwrap_or_default()
+                        ),
+                    ));
+                }
+            }
+        }
+        Err(Error::message(
+            ErrorKind::Credential,
+            "the device code expired before the sign in completed",
+        ))
+    }
+}
+
+// form_urlencoded encodes the form as "application/x-www-form-urlencoded".
+fn form_urlencoded(form: &[(&str, &str)]) -> String {
+    let mut url = Url::parse("http://localhost").unwrap();
+    url.query_pairs_mut().extend_pairs(form);
+    url.query().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use azure_core::http::headers::Headers;
+    use azure_core::http::policies::PolicyResult;
+    use azure_core::http::{Body, RawResponse, StatusCode};
+    use pretty_assertions::assert_eq;
+    use serde_json::{Value, json};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    // TokenEndpoint replies the requests with the canned responses in order, and records the
+    // grant types of the token requests, as well as when they are sent.
+    #[derive(Debug, Default)]
+    struct TokenEndpoint {
+        responses: Mutex<VecDeque<Value>>,
+        grant_types: Mutex<Vec<String>>,
+        instants: Mutex<Vec<Instant>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for TokenEndpoint {
+        async fn send(
+            &self,
+            _: &Context,
+            request: &mut Request,
+            _: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            let Body::Bytes(body) = request.body() else {
+                panic!("unexpected streaming body");
+            };
+            let mut form = Url::parse("http://localhost").unwrap();
+            form.set_query(Some(std::str::from_utf8(body).unwrap()));
+            let grant_type = form
+                .query_pairs()
+                .find(|(k, _)| k == "grant_type")
+                .map(|(_, v)| v.to_string());
+            let path = request.url().path().to_string();
+            let resp = self.responses.lock().unwrap().pop_front().unwrap();
+            let status = if resp.get("error").is_some() {
+                StatusCode::BadRequest
+            } else {
+                StatusCode::Ok
+            };
+            self.instants.lock().unwrap().push(Instant::now());
+            self.grant_types.lock().unwrap().push(match grant_type {
+                Some(grant_type) => grant_type,
+                None => path,
+            });
+            Ok(RawResponse::from_bytes(
+                status,
+                Headers::new(),
+                serde_json::to_vec(&resp).unwrap(),
+            ))
+        }
+    }
+
+    fn new_credential(
+        cache: Option<TokenCache>,
+        responses: Vec<Value>,
+    ) -> (DeviceCodeCredential, Arc<TokenEndpoint>) {
+        let endpoint = Arc::new(TokenEndpoint {
+            responses: Mutex::new(responses.into()),
+            ..TokenEndpoint::default()
+        });
+        let credential = DeviceCodeCredential::with_policies(
+            "https://login.microsoftonline.com/",
+            "tenant",
+            "client",
+            cache,
+            vec![endpoint.clone()],
+        )
+        .unwrap();
+        (credential, endpoint)
+    }
+
+    fn device_code(expires_in: i64) -> Value {
+        json!({
+            "device_code": "code",
+            "message": "sign in with the code",
+            "expires_in": expires_in,
+            "interval": 0,
+        })
+    }
+
+    fn error(error: &str) -> Value {
+        json!({"error": error, "error_description": "description"})
+    }
+
+    const SCOPES: &[&str] = &["https://management.azure.com/.default"];
+
+    #[tokio::test]
+    async fn sign_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TokenCache::with_mock_key(dir.path());
+        let (credential, endpoint) = new_credential(
+            Some(cache.clone()),
+            vec![
+                device_code(900),
+                error("authorization_pending"),
+                error("slow_down"),
+                json!({"access_token": "foo", "refresh_token": "bar", "expires_in": 3600}),
+            ],
+        );
+        let token = credential.get_token(SCOPES, None).await.unwrap();
+        assert_eq!(token.token.secret(), "foo");
+        assert_eq!(
+            *endpoint.grant_types.lock().unwrap(),
+            vec![
+                "/tenant/oauth2/v2.0/devicecode",
+                DEVICE_CODE_GRANT_TYPE,
+                DEVICE_CODE_GRANT_TYPE,
+                DEVICE_CODE_GRANT_TYPE,
+            ]
+        );
+
+        // The refresh token is redeemed by the subsequent invocation, without signing in again.
+        let (credential, endpoint) = new_credential(
+            Some(cache.clone()),
+            vec![json!({"access_token": "baz", "refresh_token": "qux", "expires_in": 3600})],
+        );
+        let token = credential.get_token(SCOPES, None).await.unwrap();
+        assert_eq!(token.token.secret(), "baz");
+        assert_eq!(
+            *endpoint.grant_types.lock().unwrap(),
+            vec![REFRESH_TOKEN_GRANT_TYPE]
+        );
+        assert_eq!(
+            cache
+                .get(&credential.cache_key)
+                .unwrap()
+                .unwrap()
+                .token
+                .secret(),
+            "qux"
+        );
+
+        // The user signs in again once the refresh token is revoked.
+        let (credential, endpoint) = new_credential(
+            Some(cache),
+            vec![
+                error("invalid_grant"),
+                device_code(900),
+                json!({"access_token": "foo", "expires_in": 3600}),
+            ],
+        );
+        let token = credential.get_token(SCOPES, None).await.unwrap();
+        assert_eq!(token.token.secret(), "foo");
+        assert_eq!(endpoint.grant_types.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn authorization_pending() {
+        // The token endpoint is polled until the user finishes the sign in.
+        let (credential, endpoint) = new_credential(
+            None,
+            vec![
+                device_code(900),
+                error("authorization_pending"),
+                error("authorization_pending"),
+                json!({"access_token": "foo", "expires_in": 3600}),
+            ],
+        );
+        let token = credential.get_token(SCOPES, None).await.unwrap();
+        assert_eq!(token.token.secret(), "foo");
+        assert_eq!(endpoint.grant_types.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn slow_down() {
+        // Each "slow_down" increases the polling interval.
+        let (credential, endpoint) = new_credential(
+            None,
+            vec![
+                device_code(900),
+                error("slow_down"),
+                error("slow_down"),
+                json!({"access_token": "foo", "expires_in": 3600}),
+            ],
+        );
+        let token = credential.get_token(SCOPES, None).await.unwrap();
+        assert_eq!(token.token.secret(), "foo");
+        let instants = endpoint.instants.lock().unwrap();
+        assert_eq!(instants.len(), 4);
+        let increment = SLOW_DOWN_INCREMENT.unsigned_abs();
+        assert!(instants[2] - instants[1] >= increment);
+        assert!(instants[3] - instants[2] >= increment * 2);
+    }
+
+    #[tokio::test]
+    async fn expired_token() {
+        // The device code expired before the user signs in.
+        let (credential, endpoint) = new_credential(
+            None,
+            vec![
+                device_code(900),
+                error("authorization_pending"),
+                error("expired_token"),
+            ],
+        );
+        let err = credential.get_token(SCOPES, None).await.unwrap_err();
+        assert!(err.to_string().contains("expired_token"), "{err}");
+        assert_eq!(endpoint.grant_types.lock().unwrap().len(), 3);
+
+        // The device code expired by its "expires_in", the token endpoint is not polled at all.
+        let (credential, endpoint) = new_credential(None, vec![device_code(0)]);
+        let err = credential.get_token(SCOPES, None).await.unwrap_err();
+        assert!(err.to_string().contains("expired"), "{err}");
+        assert_eq!(endpoint.grant_types.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn authorization_declined() {
+        // The other errors fail the sign in, rather than polling on.
+        let (credential, endpoint) = new_credential(
+            None,
+            vec![device_code(900), error("authorization_declined")],
+        );
+        let err = credential.get_token(SCOPES, None).await.unwrap_err();
+        assert!(err.to_string().contains("authorization_declined"), "{err}");
+        assert_eq!(endpoint.grant_types.lock().unwrap().len(), 2);
+
+        // The failure of the device code request is reported.
+        let (credential, endpoint) = new_credential(None, vec![error("invalid_client")]);
+        let err = credential.get_token(SCOPES, None).await.unwrap_err();
+        assert!(err.to_string().contains("invalid_client"), "{err}");
+        assert_eq!(endpoint.grant_types.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn malformed_response() {
+        // The response with neither the access token nor the error fails, rather than polling on.
+        let (credential, endpoint) =
+            new_credential(None, vec![device_code(900), json!({"expires_in": 3600})]);
+        let err = credential.get_token(SCOPES, None).await.unwrap_err();
+        assert!(err.to_string().contains("neither"));
+        assert_eq!(endpoint.grant_types.lock().unwrap().len(), 2);
+    }
+}
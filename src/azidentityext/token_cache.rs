@@ -0,0 +1,270 @@
+// The encrypted on-disk cache of the access tokens, which is shared by the consecutive invocations
+// of the CLI. The tokens are keyed by the auth mode, authority host, tenant, client and scopes.
+//
+// The cache file is encrypted by ChaCha20-Poly1305, whose key is held in the OS credential store
+// (i.e. the Keychain on macOS, the Credential Manager (DPAPI) on Windows and the Secret Service on
+// Linux), rather than next to the cache.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, anyhow, bail};
+use azure_core::credentials::{AccessToken, TokenCredential, TokenRequestOptions};
+use azure_core::time::OffsetDateTime;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Auth;
+use crate::config::Config;
+
+pub const TOKEN_CACHE_FILE: &str = "token_cache.bin";
+
+// The service and user of the key entry in the OS credential store.
+const KEY_SERVICE: &str = "az-rs";
+const KEY_USER: &str = "token-cache";
+
+// The cached tokens are regarded as expired a while ahead of their expiry, so that they don't
+// expire in the middle of the invocation.
+const EXPIRY_MARGIN_SECONDS: i64 = 300;
+
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    token: String,
+    // The unix timestamp
+    expires_on: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenCache {
+    path: PathBuf,
+    key_entry: Arc<keyring::Entry>,
+}
+
+impl TokenCache {
+    pub fn new(dir: &Path) -> Result<Self> {
+        let key_entry = keyring::Entry::new(KEY_SERVICE, KEY_USER)
+            .context("opening the token cache key in the credential store")?;
+        Ok(Self::with_key_entry(dir, key_entry))
+    }
+
+    // from_config_dir returns the cache under the config directory, if any. The failure of opening
+    // the key is logged, rather than failing the authentication.
+    pub fn from_config_dir() -> Option<Self> {
+        let dir = Config::dir()?;
+        Self::new(&dir)
+            .inspect_err(|e| tracing::warn!("opening the token cache: {e:#}"))
+            .ok()
+    }
+
+    fn with_key_entry(dir: &Path, key_entry: keyring::Entry) -> Self {
+        Self {
+            path: dir.join(TOKEN_CACHE_FILE),
+            key_entry: Arc::new(key_entry),
+        }
+    }
+
+    // with_mock_key builds the cache whose key is held in memory, instead of the OS credential
+    // store, which is not available in the tests.
+    #[cfg(test)]
+    pub fn with_mock_key(dir: &Path) -> Self {
+        let credential = keyring::mock::MockCredential::default();
+        let key_entry = keyring::Entry::new_with_credential(Box::new(credential));
+        Self::with_key_entry(dir, key_entry)
+    }
+
+    // get returns the cached token of the key, unless it is (about to be) expired.
+    pub fn get(&self, key: &str) -> Result<Option<AccessToken>> {
+        let entries = self.load()?;
+        let Some(entry) = entries.get(key) else {
+            return Ok(None);
+        };
+        if entry.expires_on - EXPIRY_MARGIN_SECONDS <= OffsetDateTime::now_utc().unix_timestamp() {
+            return Ok(None);
+        }
+        Ok(Some(AccessToken {
+            token: entry.token.clone().into(),
+            expires_on: OffsetDateTime::from_unix_timestamp(entry.expires_on)?,
+        }))
+    }
+
+    // set caches the token of the key, meanwhile the expired tokens are evicted.
+    pub fn set(&self, key: &str, token: &AccessToken) -> Result<()> {
+        // The cache that can't be decrypted (e.g. the key is removed) is simply overwritten.
+        let mut entries = self.load().unwrap_or_default();
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        entries.retain(|_, entry| entry.expires_on > now);
+        entries.insert(
+            key.to_string(),
+            Entry {
+                token: token.token.secret().to_string(),
+                expires_on: token.expires_on.unix_timestamp(),
+            },
+        );
+        self.save(&entries)
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, Entry>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e).context(format!("reading the token cache {:?}", self.path)),
+        };
+        if data.len() < NONCE_LEN {
+            bail!("the token cache {:?} is corrupted", self.path);
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("decrypting the token cache {:?}", self.path))?;
+        serde_json::from_slice(&plaintext)
+            .context(format!("parsing the token cache {:?}", self.path))
+    }
+
+    fn save(&self, entries: &BTreeMap<String, Entry>) -> Result<()> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, serde_json::to_vec(entries)?.as_slice())
+            .map_err(|_| anyhow!("encrypting the token cache"))?;
+
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir).context(format!("creating directory {dir:?}"))?;
+
+        // Write to a temporary file (only readable by the current user) and then rename it, so
+        // that the concurrent invocations never read a partial cache.
+        let mut file = tempfile::NamedTempFile::new_in(dir)
+            .context(format!("creating the token cache in {dir:?}"))?;
+        file.write_all(&nonce)?;
+        file.write_all(&ciphertext)?;
+        file.persist(&self.path)
+            .context(format!("writing the token cache {:?}", self.path))?;
+        Ok(())
+    }
+
+    // cipher builds the cipher from the key in the credential store, which is generated if not
+    // exists. If the concurrent invocations both generate the key, the cache written by the loser
+    // can't be decrypted, and is simply overwritten.
+    fn cipher(&self) -> Result<ChaCha20Poly1305> {
+        match self.key_entry.get_secret() {
+            Ok(key) if key.len() == KEY_LEN => {
+                return Ok(ChaCha20Poly1305::new(Key::from_slice(&key)));
+            }
+            Ok(_) => bail!("the token cache key in the credential store is corrupted"),
+            Err(keyring::Error::NoEntry) => {}
+            Err(e) => {
+                return Err(e).context("reading the token cache key from the credential store");
+            }
+        }
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        self.key_entry
+            .set_secret(&key)
+            .context("storing the token cache key in the credential store")?;
+        Ok(ChaCha20Poly1305::new(&key))
+    }
+}
+
+// CachedTokenCredential wraps a credential, whose tokens are cached in the token cache under the
+// config directory. The failure of the cache is logged, rather than failing the authentication.
+// It is only meant for the credentials whose principal is pinned by the auth settings.
+#[derive(Debug)]
+pub struct CachedTokenCredential {
+    credential: Arc<dyn TokenCredential>,
+    auth: Auth,
+    authority_host: String,
+    cache: Option<TokenCache>,
+}
+
+impl CachedTokenCredential {
+    pub fn new(
+        credential: Arc<dyn TokenCredential>,
+        auth: Auth,
+        authority_host: String,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            credential,
+            auth,
+            authority_host,
+            cache: TokenCache::from_config_dir(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenCredential for CachedTokenCredential {
+    async fn get_token(
+        &self,
+        scopes: &[&str],
+        options: Option<TokenRequestOptions>,
+    ) -> azure_core::Result<AccessToken> {
+        let key = self.auth.cache_key(&self.authority_host, scopes);
+        if let Some(cache) = &self.cache {
+            match cache.get(&key) {
+                Ok(Some(token)) => return Ok(token),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("reading the token cache: {e:#}"),
+            }
+        }
+        let token = self.credential.get_token(scopes, options).await?;
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.set(&key, &token) {
+                tracing::warn!("writing the token cache: {e:#}");
+            }
+        }
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use azure_core::time::Duration;
+    use pretty_assertions::assert_eq;
+
+    fn token(value: &str, expires_in: Duration) -> AccessToken {
+        AccessToken {
+            token: value.to_string().into(),
+            expires_on: OffsetDateTime::now_utc() + expires_in,
+        }
+    }
+
+    #[test]
+    fn cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TokenCache::with_mock_key(dir.path());
+        assert!(cache.get("a").unwrap().is_none());
+
+        cache.set("a", &token("foo", Duration::hours(1))).unwrap();
+        cache.set("b", &token("bar", Duration::minutes(1))).unwrap();
+        assert_eq!(cache.get("a").unwrap().unwrap().token.secret(), "foo");
+        // The token about to expire is not returned.
+        assert!(cache.get("b").unwrap().is_none());
+
+        // The cache is only readable by the current user.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(dir.path().join(TOKEN_CACHE_FILE)).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+
+        // The cache file contains no token text.
+        let data = fs::read(dir.path().join(TOKEN_CACHE_FILE)).unwrap();
+        let data = String::from_utf8_lossy(&data);
+        assert!(!data.contains("foo") && !data.contains("bar"));
+
+        // The cache can't be decrypted by another key, and is then overwritten.
+        let cache = TokenCache::with_mock_key(dir.path());
+        assert!(cache.get("a").is_err());
+        cache.set("c", &token("baz", Duration::hours(1))).unwrap();
+        assert!(cache.get("a").unwrap().is_none());
+        assert_eq!(cache.get("c").unwrap().unwrap().token.secret(), "baz");
+    }
+}
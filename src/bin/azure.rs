@@ -1,32 +1,47 @@
 use anyhow::Result;
+use az_rs::api::error::exit_code;
+use az_rs::auth::Auth;
+use az_rs::client::poller::Progress;
+use az_rs::cloud::Cloud;
 use az_rs::log::set_global_logger;
 use az_rs::run;
 use azure_core::credentials::TokenCredential;
-use azure_identity::DefaultAzureCredential;
-use std::{env, path::PathBuf, str::FromStr, sync::Arc};
+use std::{env, path::PathBuf, process::ExitCode, sync::Arc};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     set_global_logger();
 
-    let cred_func = || -> Result<Arc<dyn TokenCredential>> {
-        let cred = DefaultAzureCredential::new()?;
-        Ok(cred)
-    };
+    let cred_func =
+        |cloud: &Cloud, auth: &Auth| -> Result<Arc<dyn TokenCredential>> { auth.credential(cloud) };
 
     let result_func = |res: String| {
         println!("{res}");
     };
 
-    run(
-        PathBuf::from_str("./metadata/metadata")?,
+    let progress_func = |progress: &Progress| {
+        eprintln!(
+            "Status: {} (elapsed: {}s), polling again in {}s",
+            progress.status,
+            progress.elapsed.as_secs(),
+            progress.next_poll.as_secs()
+        );
+    };
+
+    if let Err(err) = run(
+        PathBuf::from("./metadata/metadata"),
         env::args_os()
             .into_iter()
             .map(|s| s.into_string().unwrap())
             .collect(),
         cred_func,
         result_func,
+        progress_func,
     )
-    .await?;
-    Ok(())
+    .await
+    {
+        eprintln!("Error: {err:?}");
+        return ExitCode::from(exit_code(&err));
+    }
+    ExitCode::SUCCESS
 }
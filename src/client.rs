@@ -1,21 +1,66 @@
-mod poller;
-mod response;
+pub mod policies;
+pub mod poller;
+pub mod response;
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use azure_core::{
     credentials::TokenCredential,
+    error::ErrorKind,
     http::{
         policies::{BearerTokenCredentialPolicy, Policy},
         ClientMethodOptions, ClientOptions, Context, Method, Pipeline, Request, Url,
     },
-    Result,
+    Error, Result,
 };
 use bytes::Bytes;
 
-use crate::client::poller::Poller;
+use crate::client::policies::{
+    AuxiliaryAuthorizationPolicy, LoggingPolicy, RequestHeadersPolicy, SecretProps,
+};
+use crate::client::poller::{
+    is_on_endpoint, NewPollerOptions, PollUntilDoneOptions, Poller, PollerState,
+};
 use crate::client::response::Response;
 
+#[derive(Debug, Default, Clone)]
+pub struct RunOptions<'a> {
+    pub method_options: ClientMethodOptions<'a>,
+
+    // poller_options is used to build the poller in case the operation is a LRO.
+    pub poller_options: Option<NewPollerOptions>,
+
+    // poll_options is used to wait for the LRO to complete.
+    pub poll_options: Option<PollUntilDoneOptions>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct NewClientOptions {
+    pub client_options: ClientOptions,
+
+    // The credentials of the auxiliary tenants, whose tokens are attached to every request (including
+    // the polling requests of the LRO) in the "x-ms-authorization-auxiliary" header.
+    pub aux_credentials: Vec<Arc<dyn TokenCredential>>,
+
+    // debug logs every request and response (including the polling ones of the LRO) at the debug
+    // level, which is enabled by log::enable_debug.
+    pub debug: bool,
+
+    // secret_props are the properties whose values are redacted from the debug log.
+    pub secret_props: SecretProps,
+
+    // correlation_id is sent in the "x-ms-correlation-request-id" header of every request, so that
+    // the requests of one command can be correlated. A random one is generated if not specified.
+    pub correlation_id: Option<String>,
+
+    // headers are the extra headers sent along with every request.
+    pub headers: Vec<(String, String)>,
+
+    // user_agent_suffix is appended to the "user-agent" header of every request.
+    pub user_agent_suffix: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct Client {
     endpoint: Url,
@@ -27,54 +72,208 @@ impl Client {
         endpoint: &str,
         auth_scopes: impl IntoIterator<Item = impl Into<String>>,
         credential: Arc<dyn TokenCredential>,
-        options: Option<ClientOptions>,
+        options: Option<NewClientOptions>,
     ) -> Result<Self> {
         let endpoint = Url::parse(endpoint)?;
         let options = options.unwrap_or_default();
-        let auth_policy: Arc<dyn Policy> =
-            Arc::new(BearerTokenCredentialPolicy::new(credential, auth_scopes));
+        let auth_scopes: Vec<String> = auth_scopes.into_iter().map(Into::into).collect();
+        let mut policies: Vec<Arc<dyn Policy>> = vec![Arc::new(BearerTokenCredentialPolicy::new(
+            credential,
+            auth_scopes.clone(),
+        ))];
+        if !options.aux_credentials.is_empty() {
+            policies.push(Arc::new(AuxiliaryAuthorizationPolicy::new(
+                options.aux_credentials,
+                auth_scopes,
+            )));
+        }
+        // The logging policy comes last, so that all the headers are logged.
+        let mut per_try_policies: Vec<Arc<dyn Policy>> = vec![Arc::new(RequestHeadersPolicy::new(
+            options
+                .correlation_id
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            options.headers,
+            options.user_agent_suffix,
+        ))];
+        if options.debug {
+            per_try_policies.push(Arc::new(LoggingPolicy::new(options.secret_props)));
+        }
         let pipeline = Pipeline::new(
             option_env!("CARGO_PKG_NAME"),
             option_env!("CARGO_PKG_VERSION"),
-            options,
-            vec![auth_policy],
-            vec![],
+            options.client_options,
+            policies,
+            per_try_policies,
         );
         Ok(Self { endpoint, pipeline })
     }
 
+    // run sends the request, and waits for the LRO (if any) to complete.
     pub async fn run(
         &self,
         method: Method,
         api_path: &str,
-        api_version: &str,
+        query: &HashMap<String, String>,
+        headers: &HashMap<String, String>,
         body: Option<Bytes>,
-        options: Option<ClientMethodOptions<'_>>,
+        options: Option<RunOptions<'_>>,
     ) -> Result<Response> {
         let options = options.unwrap_or_default();
+        let ctx = Context::with_context(&options.method_options.context);
+        let (resp, poller) = self
+            .send(
+                &ctx,
+                method,
+                api_path,
+                query,
+                headers,
+                body,
+                options.poller_options,
+            )
+            .await?;
+        if let Some(mut poller) = poller {
+            return poller.poll_until_done(&ctx, options.poll_options).await;
+        }
+        Ok(resp)
+    }
+
+    // begin sends the request without waiting for the LRO to complete. The poller of the
+    // in-progress LRO is returned, which can be used to get a resume token.
+    pub async fn begin(
+        &self,
+        method: Method,
+        api_path: &str,
+        query: &HashMap<String, String>,
+        headers: &HashMap<String, String>,
+        body: Option<Bytes>,
+        options: Option<RunOptions<'_>>,
+    ) -> Result<(Response, Option<Poller>)> {
+        let options = options.unwrap_or_default();
+        let ctx = Context::with_context(&options.method_options.context);
+        self.send(
+            &ctx,
+            method,
+            api_path,
+            query,
+            headers,
+            body,
+            options.poller_options,
+        )
+        .await
+    }
+
+    // resume rebuilds the poller from the resume token, and waits for the LRO to complete.
+    pub async fn resume(&self, token: &str, options: Option<RunOptions<'_>>) -> Result<Response> {
+        let options = options.unwrap_or_default();
+        let ctx = Context::with_context(&options.method_options.context);
+        let mut poller = Poller::from_resume_token(self.pipeline.clone(), &self.endpoint, token)?;
+        poller.poll_until_done(&ctx, options.poll_options).await
+    }
+
+    // poll_once rebuilds the poller from the resume token, and polls the LRO once for its current state.
+    pub async fn poll_once(
+        &self,
+        token: &str,
+        options: Option<ClientMethodOptions<'_>>,
+    ) -> Result<PollerState> {
+        let options = options.unwrap_or_default();
+        let ctx = Context::with_context(&options.context);
+        let mut poller = Poller::from_resume_token(self.pipeline.clone(), &self.endpoint, token)?;
+        poller.poll(&ctx).await?;
+        poller.state()
+    }
+
+    async fn send(
+        &self,
+        ctx: &Context<'_>,
+        method: Method,
+        api_path: &str,
+        query: &HashMap<String, String>,
+        headers: &HashMap<String, String>,
+        body: Option<Bytes>,
+        poller_options: Option<NewPollerOptions>,
+    ) -> Result<(Response, Option<Poller>)> {
         let mut url = self.endpoint.clone();
+        // The api_path can also be an absolute URL (e.g. the "nextLink" of a pageable response),
+        // which is used as is, including its query parameters (e.g. api-version), if any.
         url = url.join(api_path)?;
-        url.query_pairs_mut()
-            .append_pair("api-version", api_version);
+        // The request carries the tokens for the endpoint, which must not be sent to another host
+        // by e.g. a crafted "nextLink".
+        if !is_on_endpoint(&url, &self.endpoint) {
+            return Err(Error::message(
+                ErrorKind::Other,
+                format!("the URL {url} doesn't belong to {}", self.endpoint),
+            ));
+        }
+        let existing: HashSet<String> = url.query_pairs().map(|(k, _)| k.into_owned()).collect();
+        let pairs: Vec<_> = query
+            .iter()
+            .filter(|(k, _)| !existing.contains(*k))
+            .collect();
+        if !pairs.is_empty() {
+            url.query_pairs_mut().extend_pairs(pairs);
+        }
         let mut request = Request::new(url, method);
         request.insert_header("accept", "application/json");
+        for (k, v) in headers {
+            request.insert_header(k.to_lowercase(), v.clone());
+        }
         if let Some(body) = body {
             request.insert_header("content-type", "application/json");
             request.set_body(body);
         }
 
-        let ctx = Context::with_context(&options.context);
-        let raw_resp = self.pipeline.send(&ctx, &mut request).await?;
+        let raw_resp = self.pipeline.send(ctx, &mut request).await?;
         let resp = Response::from_raw_response(raw_resp).await?;
 
-        // For PUT, POST, PATCH, DELETE operations that can be a LRO, try to
+        // For PUT, POST, PATCH, DELETE operations that can be a LRO, try to build a poller.
         if [Method::Put, Method::Post, Method::Delete, Method::Patch].contains(&method) {
-            if let Ok(mut poller) = Poller::new(self.pipeline.clone(), &request, &resp, None).await
+            if let Ok(poller) =
+                Poller::new(self.pipeline.clone(), &request, &resp, poller_options).await
             {
-                return poller.poll_until_done(&ctx, None).await;
+                // The LRO that completes synchronously doesn't need a poller.
+                if poller.done() {
+                    return Ok((poller.result(ctx).await?, None));
+                }
+                return Ok((resp, Some(poller)));
             }
         }
 
-        Ok(resp)
+        Ok((resp, None))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::azidentityext::access_token_credential::AccessTokenCredential;
+
+    #[tokio::test]
+    async fn foreign_url() {
+        let client = Client::new(
+            "https://management.azure.com",
+            ["https://management.azure.com/.default"],
+            AccessTokenCredential::new("token".to_string()).unwrap(),
+            None,
+        )
+        .unwrap();
+        for next_link in [
+            "https://example.com/subscriptions?$skiptoken=foo",
+            "http://management.azure.com/subscriptions?$skiptoken=foo",
+            "https://management.azure.com:8443/subscriptions?$skiptoken=foo",
+        ] {
+            let err = client
+                .run(
+                    Method::Get,
+                    next_link,
+                    &HashMap::new(),
+                    &HashMap::new(),
+                    None,
+                    None,
+                )
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("doesn't belong to"), "{err}");
+        }
     }
 }
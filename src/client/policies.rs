@@ -0,0 +1,575 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use azure_core::time::{Duration, OffsetDateTime};
+use azure_core::{
+    credentials::{AccessToken, TokenCredential},
+    http::{
+        headers::{HeaderName, Headers},
+        policies::{Policy, PolicyResult},
+        Body, Context, RawResponse, Request,
+    },
+};
+use chrono::Utc;
+
+pub const AUXILIARY_AUTHORIZATION_HEADER: &str = "x-ms-authorization-auxiliary";
+pub const CORRELATION_REQUEST_ID_HEADER: &str = "x-ms-correlation-request-id";
+pub const CLIENT_REQUEST_ID_HEADER: &str = "x-ms-client-request-id";
+const USER_AGENT_HEADER: &str = "user-agent";
+
+const REDACTED: &str = "REDACTED";
+
+// The tokens of the auxiliary tenants are refreshed a while ahead of their expiry.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::minutes(5);
+
+// The properties that commonly carry the secrets, whose values are redacted when the API schema
+// (thus the properties it marks as secret) is not known, e.g. for the "lro" command.
+const WELL_KNOWN_SECRET_PROPS: &[&str] = &[
+    "password",
+    "adminPassword",
+    "administratorLoginPassword",
+    "secret",
+    "clientSecret",
+    "key",
+    "keys",
+    "primaryKey",
+    "secondaryKey",
+    "primaryMasterKey",
+    "secondaryMasterKey",
+    "accessKey",
+    "sharedKey",
+    "privateKey",
+    "connectionString",
+    "primaryConnectionString",
+    "secondaryConnectionString",
+    "token",
+    "accessToken",
+    "refreshToken",
+    "sasToken",
+];
+
+// SecretProps are the properties of the request and response body, whose values are redacted from
+// the debug log.
+#[derive(Debug, Clone)]
+pub enum SecretProps {
+    // Paths are the paths of the properties that the API schema marks as secret, i.e. the dot joined
+    // property names from the body root (e.g. "properties.osProfile.adminPassword"), where the
+    // array items share the path of the array.
+    Paths(BTreeSet<String>),
+    // WellKnown matches the well known secret property names at any level, for the commands whose
+    // API schema is not known.
+    WellKnown,
+}
+
+impl Default for SecretProps {
+    fn default() -> Self {
+        Self::Paths(BTreeSet::new())
+    }
+}
+
+impl SecretProps {
+    fn contains(&self, path: &str, name: &str) -> bool {
+        match self {
+            Self::Paths(paths) => paths.contains(path),
+            Self::WellKnown => WELL_KNOWN_SECRET_PROPS.contains(&name),
+        }
+    }
+}
+
+// The headers that carry the credentials, whose values are always redacted.
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    AUXILIARY_AUTHORIZATION_HEADER,
+    "cookie",
+    "set-cookie",
+];
+
+// The signature of the SAS token, which may appear in the URL, the headers (e.g. the
+// "location" header) and the body.
+const SAS_SIGNATURE_PARAM: &str = "sig=";
+
+// AuxiliaryAuthorizationPolicy attaches the tokens of the auxiliary tenants to the request, which
+// is required by the cross tenant operations (e.g. the cross tenant VNet peering).
+//
+// The tokens are reused by the subsequent requests (e.g. the polls of the LRO) until they are about
+// to expire, as the bearer token policy does.
+#[derive(Debug)]
+pub struct AuxiliaryAuthorizationPolicy {
+    credentials: Vec<Arc<dyn TokenCredential>>,
+    scopes: Vec<String>,
+    tokens: Mutex<Vec<Option<AccessToken>>>,
+}
+
+impl AuxiliaryAuthorizationPolicy {
+    pub fn new(
+        credentials: Vec<Arc<dyn TokenCredential>>,
+        scopes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            tokens: Mutex::new(vec![None; credentials.len()]),
+            credentials,
+            scopes: scopes.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    // cached_token returns the token of the i-th credential, unless it is about to expire.
+    fn cached_token(&self, i: usize) -> Option<AccessToken> {
+        let tokens = self.tokens.lock().unwrap();
+        tokens[i]
+            .as_ref()
+            .filter(|token| token.expires_on - TOKEN_REFRESH_MARGIN > OffsetDateTime::now_utc())
+            .cloned()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Policy for AuxiliaryAuthorizationPolicy {
+    async fn send(
+        &self,
+        ctx: &Context,
+        request: &mut Request,
+        next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        let scopes: Vec<_> = self.scopes.iter().map(String::as_str).collect();
+        let mut tokens = vec![];
+        for (i, credential) in self.credentials.iter().enumerate() {
+            let token = match self.cached_token(i) {
+                Some(token) => token,
+                None => {
+                    let token = credential.get_token(&scopes, None).await?;
+                    self.tokens.lock().unwrap()[i] = Some(token.clone());
+                    token
+                }
+            };
+            tokens.push(format!("Bearer {}", token.token.secret()));
+        }
+        request.insert_header(AUXILIARY_AUTHORIZATION_HEADER, tokens.join(", "));
+        next[0].send(ctx, request, &next[1..]).await
+    }
+}
+
+// RequestHeadersPolicy sets the headers for tracing the requests in the ARM activity logs, i.e.
+// the correlation id that is shared by all the requests of one command (including the polling ones
+// of the LRO), the client request id of each request and the user agent suffix. It also sets the
+// extra headers specified by the user.
+//
+// It is a per-try policy, so that it runs after the SDK's per-call policies (e.g. the one setting
+// the user agent).
+#[derive(Debug)]
+pub struct RequestHeadersPolicy {
+    correlation_id: String,
+    headers: Vec<(String, String)>,
+    user_agent_suffix: Option<String>,
+}
+
+impl RequestHeadersPolicy {
+    pub fn new(
+        correlation_id: String,
+        headers: Vec<(String, String)>,
+        user_agent_suffix: Option<String>,
+    ) -> Self {
+        Self {
+            correlation_id,
+            headers,
+            user_agent_suffix,
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Policy for RequestHeadersPolicy {
+    async fn send(
+        &self,
+        ctx: &Context,
+        request: &mut Request,
+        next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        request.insert_header(CORRELATION_REQUEST_ID_HEADER, self.correlation_id.clone());
+
+        // The retries of the same request share the client request id.
+        if request
+            .headers()
+            .get_optional_str(&HeaderName::from_static(CLIENT_REQUEST_ID_HEADER))
+            .is_none()
+        {
+            request.insert_header(CLIENT_REQUEST_ID_HEADER, uuid::Uuid::new_v4().to_string());
+        }
+
+        if let Some(suffix) = &self.user_agent_suffix {
+            let user_agent = request
+                .headers()
+                .get_optional_str(&HeaderName::from_static(USER_AGENT_HEADER))
+                .unwrap_or_default()
+                .to_string();
+            if !user_agent.ends_with(suffix.as_str()) {
+                request.insert_header(
+                    USER_AGENT_HEADER,
+                    format!("{user_agent} {suffix}").trim_start().to_string(),
+                );
+            }
+        }
+
+        for (k, v) in &self.headers {
+            request.insert_header(k.clone(), v.clone());
+        }
+        next[0].send(ctx, request, &next[1..]).await
+    }
+}
+
+// LoggingPolicy logs the HTTP request and response for debugging, with the secrets redacted. The
+// secrets include the credential headers, the SAS signatures and the properties that the API
+// schema marks as secret.
+//
+// It is a per-try policy, so that every retry of the initial request, as well as every polling
+// request of the LRO, is logged.
+#[derive(Debug, Default)]
+pub struct LoggingPolicy {
+    secret_props: SecretProps,
+}
+
+impl LoggingPolicy {
+    pub fn new(secret_props: SecretProps) -> Self {
+        Self { secret_props }
+    }
+
+    fn redact_body(&self, body: &[u8]) -> String {
+        if body.is_empty() {
+            return String::new();
+        }
+        match serde_json::from_slice::<serde_json::Value>(body) {
+            Ok(mut v) => {
+                redact_json(&mut v, "", &self.secret_props);
+                redact_sas(&serde_json::to_string_pretty(&v).unwrap_or_default())
+            }
+            Err(_) => redact_sas(&String::from_utf8_lossy(body)),
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Policy for LoggingPolicy {
+    async fn send(
+        &self,
+        ctx: &Context,
+        request: &mut Request,
+        next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        let body = match request.body() {
+            Body::Bytes(b) => self.redact_body(b),
+            #[allow(unreachable_patterns)]
+            _ => "<stream>".to_string(),
+        };
+        tracing::debug!(
+            "Request: {} {}\n{}\n{}",
+            request.method(),
+            redact_sas(request.url().as_str()),
+            redact_headers(request.headers()),
+            body,
+        );
+
+        let start = Utc::now();
+        let resp = next[0].send(ctx, request, &next[1..]).await;
+        let elapsed = (Utc::now() - start).num_milliseconds();
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e) => {
+                tracing::debug!("Response: error after {elapsed}ms: {e}");
+                return Err(e);
+            }
+        };
+
+        let (status, headers, body) = resp.deconstruct();
+        let body = body.collect().await?;
+        tracing::debug!(
+            "Response: {} in {elapsed}ms\n{}\n{}",
+            u16::from(status),
+            redact_headers(&headers),
+            self.redact_body(&body),
+        );
+        Ok(RawResponse::from_bytes(status, headers, body))
+    }
+}
+
+fn redact_headers(headers: &Headers) -> String {
+    headers
+        .iter()
+        .map(|(k, v)| {
+            let v = if SECRET_HEADERS.contains(&k.as_str()) {
+                REDACTED.to_string()
+            } else {
+                redact_sas(v.as_str())
+            };
+            format!("{}: {}", k.as_str(), v)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// redact_sas redacts the signatures of the SAS tokens in the text, i.e. the value of the "sig"
+// query parameter.
+fn redact_sas(s: &str) -> String {
+    let lower = s.to_ascii_lowercase();
+    let mut out = String::new();
+    let mut pos = 0;
+    while let Some(idx) = lower[pos..].find(SAS_SIGNATURE_PARAM).map(|idx| idx + pos) {
+        let value_start = idx + SAS_SIGNATURE_PARAM.len();
+        // Only the query parameter is regarded as the signature.
+        if !matches!(s[..idx].chars().last(), Some('?') | Some('&')) {
+            out.push_str(&s[pos..value_start]);
+            pos = value_start;
+            continue;
+        }
+        let value_end = s[value_start..]
+            .find(|c: char| matches!(c, '&' | '"' | '\'' | '\\' | '#') || c.is_whitespace())
+            .map_or(s.len(), |i| i + value_start);
+        out.push_str(&s[pos..value_start]);
+        out.push_str(REDACTED);
+        pos = value_end;
+    }
+    out.push_str(&s[pos..]);
+    out
+}
+
+// redact_json redacts the values of the secret properties under the JSON value, which is at the
+// path of the body.
+fn redact_json(v: &mut serde_json::Value, path: &str, secret_props: &SecretProps) {
+    match v {
+        serde_json::Value::Object(obj) => {
+            for (k, v) in obj.iter_mut() {
+                let prop_path = if path.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{path}.{k}")
+                };
+                if secret_props.contains(&prop_path, k) && !v.is_null() {
+                    *v = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(v, &prop_path, secret_props);
+                }
+            }
+        }
+        serde_json::Value::Array(arr) => {
+            for v in arr {
+                redact_json(v, path, secret_props);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use azure_core::http::{Method, StatusCode, Url};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    // RecordPolicy records the headers of the requests, instead of sending them.
+    #[derive(Debug, Default)]
+    struct RecordPolicy {
+        requests: Mutex<Vec<Headers>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for RecordPolicy {
+        async fn send(
+            &self,
+            _: &Context,
+            request: &mut Request,
+            _: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            self.requests
+                .lock()
+                .unwrap()
+                .push(request.headers().clone());
+            Ok(RawResponse::from_bytes(
+                StatusCode::Ok,
+                Headers::new(),
+                vec![],
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn request_headers() {
+        let record = Arc::new(RecordPolicy::default());
+        let policies: Vec<Arc<dyn Policy>> = vec![
+            Arc::new(RequestHeadersPolicy::new(
+                "correlation".to_string(),
+                vec![("x-foo".to_string(), "bar".to_string())],
+                Some("suffix".to_string()),
+            )),
+            record.clone(),
+        ];
+        let ctx = Context::new();
+        let url = Url::parse("https://management.azure.com/subscriptions").unwrap();
+
+        let mut request = Request::new(url.clone(), Method::Get);
+        request.insert_header(USER_AGENT_HEADER, "azsdk-rust-az-rs/0.1.0");
+        policies[0]
+            .send(&ctx, &mut request, &policies[1..])
+            .await
+            .unwrap();
+        // The retry doesn't append the suffix again.
+        policies[0]
+            .send(&ctx, &mut request, &policies[1..])
+            .await
+            .unwrap();
+        policies[0]
+            .send(&ctx, &mut Request::new(url, Method::Get), &policies[1..])
+            .await
+            .unwrap();
+
+        let requests = record.requests.lock().unwrap();
+        let header = |i: usize, name: &'static str| {
+            requests[i]
+                .get_optional_str(&HeaderName::from_static(name))
+                .map(String::from)
+        };
+        for i in 0..3 {
+            assert_eq!(
+                header(i, CORRELATION_REQUEST_ID_HEADER).as_deref(),
+                Some("correlation")
+            );
+            assert_eq!(header(i, "x-foo").as_deref(), Some("bar"));
+        }
+        assert_eq!(
+            header(1, USER_AGENT_HEADER).as_deref(),
+            Some("azsdk-rust-az-rs/0.1.0 suffix")
+        );
+        assert_eq!(header(2, USER_AGENT_HEADER).as_deref(), Some("suffix"));
+        assert_eq!(
+            header(0, CLIENT_REQUEST_ID_HEADER),
+            header(1, CLIENT_REQUEST_ID_HEADER)
+        );
+        assert_ne!(
+            header(1, CLIENT_REQUEST_ID_HEADER),
+            header(2, CLIENT_REQUEST_ID_HEADER)
+        );
+    }
+
+    // CountingCredential issues the tokens expiring in the given duration, and counts them.
+    #[derive(Debug)]
+    struct CountingCredential {
+        expires_in: Duration,
+        count: Mutex<usize>,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenCredential for CountingCredential {
+        async fn get_token(
+            &self,
+            _: &[&str],
+            _: Option<azure_core::credentials::TokenRequestOptions>,
+        ) -> azure_core::Result<AccessToken> {
+            let mut count = self.count.lock().unwrap();
+            *count += 1;
+            Ok(AccessToken {
+                token: format!("token{count}").into(),
+                expires_on: OffsetDateTime::now_utc() + self.expires_in,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn auxiliary_tokens() {
+        let long_lived = Arc::new(CountingCredential {
+            expires_in: Duration::hours(1),
+            count: Mutex::new(0),
+        });
+        let short_lived = Arc::new(CountingCredential {
+            expires_in: Duration::minutes(1),
+            count: Mutex::new(0),
+        });
+        let record = Arc::new(RecordPolicy::default());
+        let policies: Vec<Arc<dyn Policy>> = vec![
+            Arc::new(AuxiliaryAuthorizationPolicy::new(
+                vec![long_lived.clone(), short_lived.clone()],
+                ["https://management.azure.com/.default"],
+            )),
+            record.clone(),
+        ];
+        let url = Url::parse("https://management.azure.com/subscriptions").unwrap();
+        for _ in 0..2 {
+            policies[0]
+                .send(
+                    &Context::new(),
+                    &mut Request::new(url.clone(), Method::Get),
+                    &policies[1..],
+                )
+                .await
+                .unwrap();
+        }
+
+        // The token about to expire is acquired for every request.
+        assert_eq!(*long_lived.count.lock().unwrap(), 1);
+        assert_eq!(*short_lived.count.lock().unwrap(), 2);
+        let requests = record.requests.lock().unwrap();
+        assert_eq!(
+            requests[1].get_optional_str(&HeaderName::from_static(AUXILIARY_AUTHORIZATION_HEADER)),
+            Some("Bearer token1, Bearer token2")
+        );
+    }
+
+    #[test]
+    fn redact() {
+        assert_eq!(
+            redact_sas("https://acct.blob.core.windows.net/c/b?sv=2022-11-02&sig=abc%2Bdef&se=x"),
+            "https://acct.blob.core.windows.net/c/b?sv=2022-11-02&sig=REDACTED&se=x"
+        );
+        assert_eq!(
+            redact_sas(r#"{"url":"https://x?sig=abc"}"#),
+            r#"{"url":"https://x?sig=REDACTED"}"#
+        );
+        assert_eq!(
+            redact_sas("config=foo&signature=bar"),
+            "config=foo&signature=bar"
+        );
+
+        // The secret properties are matched by their paths, rather than their names.
+        let mut v = json!({
+            "properties": {
+                "administratorLoginPassword": "secret",
+                "administratorLogin": "admin",
+                "users": [{"password": "secret", "name": "foo"}],
+                "keyData": null,
+                "tags": {"password": "not a secret"},
+            }
+        });
+        redact_json(
+            &mut v,
+            "",
+            &SecretProps::Paths(BTreeSet::from([
+                "properties.administratorLoginPassword".to_string(),
+                "properties.users.password".to_string(),
+                "properties.keyData".to_string(),
+            ])),
+        );
+        assert_eq!(
+            v,
+            json!({
+                "properties": {
+                    "administratorLoginPassword": "REDACTED",
+                    "administratorLogin": "admin",
+                    "users": [{"password": "REDACTED", "name": "foo"}],
+                    "keyData": null,
+                    "tags": {"password": "not a secret"},
+                }
+            })
+        );
+
+        let mut v = json!({"keys": [{"keyName": "key1", "value": "secret"}], "name": "foo"});
+        redact_json(&mut v, "", &SecretProps::WellKnown);
+        assert_eq!(v, json!({"keys": "REDACTED", "name": "foo"}));
+
+        let mut headers = Headers::new();
+        headers.insert("authorization", "Bearer token");
+        headers.insert("location", "https://x?sig=abc");
+        let headers = redact_headers(&headers);
+        assert!(headers.contains("authorization: REDACTED"));
+        assert!(headers.contains("location: https://x?sig=REDACTED"));
+    }
+}
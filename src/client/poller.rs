@@ -6,11 +6,18 @@ mod op;
 mod utils;
 
 use azure_core::error::ErrorKind;
-use azure_core::http::{Method, Request};
+use azure_core::http::{headers::Headers, Method, Request, Url};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bytes::Bytes;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::rc::Rc;
 use std::time::Duration;
 use typespec_client_core::sleep::sleep;
 use typespec_client_core::time;
-use utils::FinalStateVia;
+
+pub use utils::{FinalStateVia, LROStatus};
 
 use azure_core::Error;
 use azure_core::{
@@ -31,6 +38,9 @@ trait PollingHandler {
 
     // result must be called once the LRO has reached a terminal state. It returns result of the operation.
     async fn result(&self, ctx: &Context<'_>) -> Result<Response>;
+
+    // status returns the current status of the LRO.
+    fn status(&self) -> LROStatus;
 }
 
 enum Handler {
@@ -45,19 +55,158 @@ enum Handler {
 pub struct NewPollerOptions {
     // final_state contains the final-state-via value for the LRO.
     // NOTE: used only for Azure-AsyncOperation and Operation-Location LROs.
-    final_state: Option<FinalStateVia>,
+    pub final_state: Option<FinalStateVia>,
 
     // operation_location_result_path contains the JSON path to the result's
     // payload when it's included with the terminal success response.
     // NOTE: only used for Operation-Location LROs.
-    operation_location_result_path: Option<String>,
+    pub operation_location_result_path: Option<String>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Clone, Default)]
 pub struct PollUntilDoneOptions {
     // frequency is the time to wait between polling intervals in absence of a Retry-After header. Allowed minimum is one second.
     // Pass zero to accept the default value (30s).
-    frequency: Option<Duration>,
+    pub frequency: Option<Duration>,
+
+    // timeout is the overall deadline for the LRO to complete, counting from the first poll.
+    pub timeout: Option<Duration>,
+
+    // progress is called with the intermediate state of the LRO before each wait.
+    pub progress: Option<ProgressFunc>,
+}
+
+impl Debug for PollUntilDoneOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PollUntilDoneOptions")
+            .field("frequency", &self.frequency)
+            .field("timeout", &self.timeout)
+            .field("progress", &self.progress.as_ref().map(|_| "<func>"))
+            .finish()
+    }
+}
+
+pub type ProgressFunc = Rc<dyn Fn(&Progress)>;
+
+// Progress is the intermediate state of an in-progress LRO.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    pub status: LROStatus,
+
+    // The time elapsed since the polling started.
+    pub elapsed: Duration,
+
+    // The time to wait before the next poll.
+    pub next_poll: Duration,
+}
+
+// HandlerKind identifies the polling handler in the resume token.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HandlerKind {
+    AsyncOp,
+    Loc,
+    Op,
+    Body,
+}
+
+// ResumeToken contains the necessary information to rebuild a poller for an in-progress LRO, e.g.
+// in another process.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumeToken {
+    pub handler: HandlerKind,
+
+    // The HTTP method of the initial LRO request.
+    pub method: String,
+
+    // The URL of the initial LRO request.
+    pub origin_url: String,
+
+    // The URL for polling, whose meaning depends on the handler.
+    pub poll_url: String,
+
+    // The URL from the Location header, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loc_url: Option<String>,
+
+    // The value of final-state-via from swagger.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_state: Option<FinalStateVia>,
+
+    // The JSON path to the result's payload when it's included with the terminal success response.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_path: Option<String>,
+}
+
+impl ResumeToken {
+    pub fn encode(&self) -> Result<String> {
+        Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(self)?))
+    }
+
+    pub fn decode(token: &str) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(token.trim()).map_err(|e| {
+            Error::new(ErrorKind::DataConversion, e).context("decoding the resume token")
+        })?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub fn method(&self) -> Result<Method> {
+        match self.method.as_str() {
+            "GET" => Ok(Method::Get),
+            "PUT" => Ok(Method::Put),
+            "PATCH" => Ok(Method::Patch),
+            "POST" => Ok(Method::Post),
+            "DELETE" => Ok(Method::Delete),
+            "HEAD" => Ok(Method::Head),
+            m => Err(Error::message(
+                ErrorKind::DataConversion,
+                format!("unknown HTTP method {m:?} in the resume token"),
+            )),
+        }
+    }
+
+    pub fn parse_url(url: &str) -> Result<Url> {
+        Ok(Url::parse(url)?)
+    }
+
+    // validate_endpoint ensures that all the URLs in the token are on the endpoint. The polling
+    // requests carry the tokens for the endpoint, which must not be sent to another host by a
+    // crafted resume token.
+    pub fn validate_endpoint(&self, endpoint: &Url) -> Result<()> {
+        let urls = [
+            Some(&self.origin_url),
+            Some(&self.poll_url),
+            self.loc_url.as_ref(),
+        ];
+        for url in urls.into_iter().flatten() {
+            if !is_on_endpoint(&Self::parse_url(url)?, endpoint) {
+                return Err(Error::message(
+                    ErrorKind::DataConversion,
+                    format!("the URL {url:?} in the resume token doesn't belong to {endpoint}"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// is_on_endpoint tells whether the URL has the same scheme, host and port as the endpoint, i.e. the
+// credentials for the endpoint can be sent to it.
+pub fn is_on_endpoint(url: &Url, endpoint: &Url) -> bool {
+    url.scheme() == endpoint.scheme()
+        && url.host_str() == endpoint.host_str()
+        && url.port_or_known_default() == endpoint.port_or_known_default()
+}
+
+// PollerState is the current state of the poller, which is reported to the user when the LRO is
+// not (yet) waited to the end.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PollerState {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<String>,
 }
 
 pub struct Poller {
@@ -102,7 +251,7 @@ impl Poller {
                 opts.operation_location_result_path,
             )?)
         } else if loc::Poller::applicable(req, resp) {
-            Handler::Loc(loc::Poller::new(pl, resp.clone())?)
+            Handler::Loc(loc::Poller::new(pl, req, resp.clone())?)
         } else if body::Poller::applicable(req, resp) {
             // must test body poller last as it's a subset of the other pollers.
             // TODO: this is ambiguous for PATCH/PUT if it returns a 200 with no polling headers (sync completion)
@@ -128,6 +277,62 @@ impl Poller {
         })
     }
 
+    // from_resume_token rebuilds the poller of an in-progress LRO from the resume token, whose URLs
+    // must belong to the endpoint.
+    pub fn from_resume_token(pl: Pipeline, endpoint: &Url, token: &str) -> Result<Self> {
+        let token = ResumeToken::decode(token)?;
+        token.validate_endpoint(endpoint)?;
+        let handler = match token.handler {
+            HandlerKind::AsyncOp => {
+                Handler::AsyncOp(asyncop::Poller::from_resume_token(pl, &token)?)
+            }
+            HandlerKind::Loc => Handler::Loc(loc::Poller::from_resume_token(pl, &token)?),
+            HandlerKind::Op => Handler::Op(op::Poller::from_resume_token(pl, &token)?),
+            HandlerKind::Body => Handler::Body(body::Poller::from_resume_token(pl, &token)?),
+        };
+        Ok(Self {
+            handler,
+            resp: empty_response(),
+        })
+    }
+
+    // resume_token returns the token that can be used to rebuild the poller.
+    // It returns None for the LRO that is completed synchronously.
+    pub fn resume_token(&self) -> Result<Option<String>> {
+        let token = match &self.handler {
+            Handler::AsyncOp(poller) => poller.resume_token(),
+            Handler::Loc(poller) => poller.resume_token(),
+            Handler::Op(poller) => poller.resume_token(),
+            Handler::Body(poller) => poller.resume_token(),
+            Handler::Noop(_) => return Ok(None),
+        };
+        Ok(Some(token.encode()?))
+    }
+
+    // state returns the current state of the poller, the resume token is omitted once the LRO has
+    // reached a terminal state.
+    pub fn state(&self) -> Result<PollerState> {
+        let resume_token = if self.done() {
+            None
+        } else {
+            self.resume_token()?
+        };
+        Ok(PollerState {
+            status: self.status().to_string(),
+            resume_token,
+        })
+    }
+
+    pub fn status(&self) -> LROStatus {
+        match &self.handler {
+            Handler::AsyncOp(poller) => poller.status(),
+            Handler::Loc(poller) => poller.status(),
+            Handler::Op(poller) => poller.status(),
+            Handler::Body(poller) => poller.status(),
+            Handler::Noop(poller) => poller.status(),
+        }
+    }
+
     pub async fn poll(&mut self, ctx: &Context<'_>) -> Result<Response> {
         if self.done() {
             return Ok(self.resp.clone());
@@ -151,6 +356,11 @@ impl Poller {
         opts: Option<PollUntilDoneOptions>,
     ) -> Result<Response> {
         let opts = opts.unwrap_or_default();
+        let frequency = opts
+            .frequency
+            .filter(|v| !v.is_zero())
+            .unwrap_or(Duration::from_secs(30));
+        let start = Utc::now();
 
         loop {
             let resp = self.poll(ctx).await?;
@@ -158,15 +368,47 @@ impl Poller {
                 return self.result(ctx).await;
             }
 
-            let duration = time::Duration::try_from(
-                utils::retry_after(&resp)
-                    .unwrap_or(opts.frequency.unwrap_or(Duration::from_secs(30))),
-            )
-            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+            let elapsed = (Utc::now() - start).to_std().unwrap_or_default();
+            let mut delay = utils::retry_after(&resp).unwrap_or(frequency);
+            if let Some(timeout) = opts.timeout {
+                if elapsed >= timeout {
+                    return Err(Error::message(
+                        ErrorKind::Other,
+                        format!(
+                            "timed out after {}s waiting for the long running operation to complete (polling URL: {})",
+                            timeout.as_secs(),
+                            self.polling_url().unwrap_or_default(),
+                        ),
+                    ));
+                }
+                delay = delay.min(timeout - elapsed);
+            }
+            if let Some(progress) = &opts.progress {
+                progress(&Progress {
+                    status: self.status(),
+                    elapsed,
+                    next_poll: delay,
+                });
+            }
+
+            let duration =
+                time::Duration::try_from(delay).map_err(|e| Error::new(ErrorKind::Other, e))?;
             sleep(duration).await;
         }
     }
 
+    // polling_url returns the URL used to poll the LRO, if any.
+    pub fn polling_url(&self) -> Option<String> {
+        let token = match &self.handler {
+            Handler::AsyncOp(poller) => poller.resume_token(),
+            Handler::Loc(poller) => poller.resume_token(),
+            Handler::Op(poller) => poller.resume_token(),
+            Handler::Body(poller) => poller.resume_token(),
+            Handler::Noop(_) => return None,
+        };
+        Some(token.poll_url)
+    }
+
     pub fn done(&self) -> bool {
         match &self.handler {
             Handler::AsyncOp(poller) => poller.done(),
@@ -180,7 +422,7 @@ impl Poller {
     // result returns the final response of the LRO operation when it reaches a terminal state.
     // If the LRO completed successfully, the Response is returned (which can be None).
     // If the LRO failed or was canceled, an Error of ErrorKind::HttpResponse is returned.
-    async fn result(&self, ctx: &Context<'_>) -> Result<Response> {
+    pub async fn result(&self, ctx: &Context<'_>) -> Result<Response> {
         assert!(self.done());
         match &self.handler {
             Handler::AsyncOp(poller) => poller.result(ctx).await,
@@ -191,3 +433,253 @@ impl Poller {
         }
     }
 }
+
+// empty_response is the placeholder of the last response for the poller rebuilt from the resume token.
+fn empty_response() -> Response {
+    Response {
+        status_code: StatusCode::Accepted,
+        headers: Headers::new(),
+        body: Bytes::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use azure_core::http::RawResponse;
+    use azure_core::http::policies::{Policy, PolicyResult};
+    use pretty_assertions::assert_eq;
+    use serde_json::{Value, json};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    const ORIGIN_URL: &str =
+        "https://management.azure.com/subscriptions/sub/providers/Microsoft.Foo/foos/foo";
+    const OPERATION_URL: &str = "https://management.azure.com/operations/op";
+    const LOCATION_URL: &str = "https://management.azure.com/operationResults/op";
+
+    // CannedPolicy replies the requests with the canned bodies of their URLs, instead of sending
+    // them, and records the requested URLs.
+    #[derive(Debug)]
+    struct CannedPolicy {
+        bodies: HashMap<&'static str, Value>,
+        urls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for CannedPolicy {
+        async fn send(
+            &self,
+            _: &Context,
+            request: &mut Request,
+            _: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            let url = request.url().to_string();
+            let body = self
+                .bodies
+                .get(url.as_str())
+                .unwrap_or_else(|| panic!("unexpected request to {url}"));
+            self.urls.lock().unwrap().push(url);
+            Ok(RawResponse::from_bytes(
+                StatusCode::Ok,
+                Headers::new(),
+                serde_json::to_vec(body).unwrap(),
+            ))
+        }
+    }
+
+    // run_lro polls the LRO started by the 202 response with the headers to the end, and returns
+    // its result along with the URLs requested.
+    async fn run_lro(
+        method: Method,
+        headers: &[(&'static str, &'static str)],
+        opts: NewPollerOptions,
+    ) -> (Value, Vec<String>) {
+        let policy = Arc::new(CannedPolicy {
+            bodies: HashMap::from([
+                (ORIGIN_URL, json!({"name": "origin"})),
+                (
+                    OPERATION_URL,
+                    json!({"status": "Succeeded", "result": {"name": "result"}}),
+                ),
+                (LOCATION_URL, json!({"name": "location"})),
+            ]),
+            urls: Mutex::new(vec![]),
+        });
+        let pl = Pipeline::new(None, None, Default::default(), vec![], vec![policy.clone()]);
+        let req = Request::new(Url::parse(ORIGIN_URL).unwrap(), method);
+        let mut resp_headers = Headers::new();
+        for (k, v) in headers {
+            resp_headers.insert(*k, *v);
+        }
+        let resp = Response {
+            status_code: StatusCode::Accepted,
+            headers: resp_headers,
+            body: Bytes::new(),
+        };
+        let mut poller = Poller::new(pl, &req, &resp, Some(opts)).await.unwrap();
+        let result = poller.poll_until_done(&Context::new(), None).await.unwrap();
+        let urls = policy.urls.lock().unwrap().clone();
+        (serde_json::from_slice(&result.body).unwrap(), urls)
+    }
+
+    #[tokio::test]
+    async fn final_state_via() {
+        let async_op = [
+            ("azure-asyncoperation", OPERATION_URL),
+            ("location", LOCATION_URL),
+        ];
+        let op_loc = [
+            ("operation-location", OPERATION_URL),
+            ("location", LOCATION_URL),
+        ];
+        let final_state = |final_state| NewPollerOptions {
+            final_state: Some(final_state),
+            ..Default::default()
+        };
+        let cases = [
+            // The result is the operation status itself.
+            (
+                Method::Post,
+                &async_op[..],
+                final_state(FinalStateVia::AzureAsyncOp),
+                json!({"status": "Succeeded", "result": {"name": "result"}}),
+                vec![OPERATION_URL],
+            ),
+            (
+                Method::Post,
+                &async_op[..],
+                final_state(FinalStateVia::Location),
+                json!({"name": "location"}),
+                vec![OPERATION_URL, LOCATION_URL],
+            ),
+            (
+                Method::Post,
+                &async_op[..],
+                final_state(FinalStateVia::OriginalUri),
+                json!({"name": "origin"}),
+                vec![OPERATION_URL, ORIGIN_URL],
+            ),
+            // The PUT always gets the result from the original URL.
+            (
+                Method::Put,
+                &async_op[..],
+                NewPollerOptions::default(),
+                json!({"name": "origin"}),
+                vec![OPERATION_URL, ORIGIN_URL],
+            ),
+            (
+                Method::Post,
+                &op_loc[..],
+                final_state(FinalStateVia::Location),
+                json!({"name": "location"}),
+                vec![OPERATION_URL, LOCATION_URL],
+            ),
+            (
+                Method::Put,
+                &op_loc[..],
+                final_state(FinalStateVia::OriginalUri),
+                json!({"name": "origin"}),
+                vec![OPERATION_URL, ORIGIN_URL],
+            ),
+            // Without the final GET, the result is extracted from the operation status.
+            (
+                Method::Post,
+                &op_loc[..1],
+                NewPollerOptions {
+                    operation_location_result_path: Some("result".to_string()),
+                    ..Default::default()
+                },
+                json!({"name": "result"}),
+                vec![OPERATION_URL],
+            ),
+            // The Location is polled until it returns the result.
+            (
+                Method::Delete,
+                &async_op[1..],
+                NewPollerOptions::default(),
+                json!({"name": "location"}),
+                vec![LOCATION_URL],
+            ),
+        ];
+        for (method, headers, opts, expect_body, expect_urls) in cases {
+            let (body, urls) = run_lro(method, headers, opts.clone()).await;
+            assert_eq!(body, expect_body, "{method:?} {headers:?} {opts:?}");
+            assert_eq!(urls, expect_urls, "{method:?} {headers:?} {opts:?}");
+        }
+    }
+
+    #[test]
+    fn resume_token_round_trip() {
+        let token = ResumeToken {
+            handler: HandlerKind::AsyncOp,
+            method: Method::Put.to_string(),
+            origin_url: "https://management.azure.com/subscriptions/sub/resourceGroups/rg"
+                .to_string(),
+            poll_url: "https://management.azure.com/operations/op".to_string(),
+            loc_url: None,
+            final_state: Some(FinalStateVia::OriginalUri),
+            result_path: None,
+        };
+        let encoded = token.encode().unwrap();
+        assert_eq!(ResumeToken::decode(&encoded).unwrap(), token);
+        assert_eq!(token.method().unwrap(), Method::Put);
+        assert!(ResumeToken::decode("not a token").is_err());
+    }
+
+    #[test]
+    fn resume_token_endpoint() {
+        let endpoint = Url::parse("https://management.azure.com").unwrap();
+        let token = ResumeToken {
+            handler: HandlerKind::Loc,
+            method: Method::Delete.to_string(),
+            origin_url: "https://management.azure.com/subscriptions/sub/resourceGroups/rg"
+                .to_string(),
+            poll_url: "https://management.azure.com/operations/op".to_string(),
+            loc_url: Some("https://management.azure.com/operations/op".to_string()),
+            final_state: None,
+            result_path: None,
+        };
+        assert!(token.validate_endpoint(&endpoint).is_ok());
+
+        let foreign = ResumeToken {
+            poll_url: "https://attacker.example.com/operations/op".to_string(),
+            ..token.clone()
+        };
+        assert!(foreign.validate_endpoint(&endpoint).is_err());
+        assert!(
+            Poller::from_resume_token(
+                Pipeline::new(None, None, Default::default(), vec![], vec![]),
+                &endpoint,
+                &foreign.encode().unwrap(),
+            )
+            .is_err()
+        );
+
+        let insecure = ResumeToken {
+            loc_url: Some("http://management.azure.com/operations/op".to_string()),
+            ..token.clone()
+        };
+        assert!(insecure.validate_endpoint(&endpoint).is_err());
+
+        // The http endpoint of a custom cloud.
+        let endpoint = Url::parse("http://localhost:8080").unwrap();
+        let local = ResumeToken {
+            origin_url: "http://localhost:8080/subscriptions/sub/resourceGroups/rg".to_string(),
+            poll_url: "http://localhost:8080/operations/op".to_string(),
+            loc_url: None,
+            ..token
+        };
+        assert!(local.validate_endpoint(&endpoint).is_ok());
+        let other_port = ResumeToken {
+            poll_url: "http://localhost:8081/operations/op".to_string(),
+            ..local.clone()
+        };
+        assert!(other_port.validate_endpoint(&endpoint).is_err());
+        let https = ResumeToken {
+            poll_url: "https://localhost:8080/operations/op".to_string(),
+            ..local
+        };
+        assert!(https.validate_endpoint(&endpoint).is_err());
+    }
+}
@@ -7,7 +7,7 @@ use azure_core::{Error, Result};
 use crate::client::poller::utils::{self, get_lro_status, result_helper};
 
 use super::utils::{get_provisioning_state, FinalStateVia, LROStatus};
-use super::{PollingHandler, Response};
+use super::{HandlerKind, PollingHandler, Response, ResumeToken};
 
 pub struct Poller {
     pl: Pipeline,
@@ -60,6 +60,35 @@ impl Poller {
             cur_state,
         })
     }
+
+    pub fn from_resume_token(pl: Pipeline, token: &ResumeToken) -> Result<Self> {
+        Ok(Self {
+            pl,
+            resp: super::empty_response(),
+            async_url: ResumeToken::parse_url(&token.poll_url)?,
+            loc_url: token
+                .loc_url
+                .as_deref()
+                .map(ResumeToken::parse_url)
+                .transpose()?,
+            origin_url: ResumeToken::parse_url(&token.origin_url)?,
+            method: token.method()?,
+            final_state: token.final_state,
+            cur_state: LROStatus::InProgress,
+        })
+    }
+
+    pub fn resume_token(&self) -> ResumeToken {
+        ResumeToken {
+            handler: HandlerKind::AsyncOp,
+            method: self.method.to_string(),
+            origin_url: self.origin_url.to_string(),
+            poll_url: self.async_url.to_string(),
+            loc_url: self.loc_url.as_ref().map(|url| url.to_string()),
+            final_state: self.final_state,
+            result_path: None,
+        }
+    }
 }
 
 impl PollingHandler for Poller {
@@ -97,6 +126,10 @@ impl PollingHandler for Poller {
         self.cur_state.is_terminal()
     }
 
+    fn status(&self) -> LROStatus {
+        self.cur_state
+    }
+
     async fn result(&self, ctx: &Context<'_>) -> Result<Response> {
         assert!(self.cur_state.is_terminal());
 
@@ -114,20 +147,18 @@ impl PollingHandler for Poller {
                 // for PATCH and PUT, the final GET is on the original resource URL
                 req = Some(Request::new(self.origin_url.clone(), Method::Get));
             }
-            Method::Post => {
-                if let Some(final_state) = self.final_state {
-                    match final_state {
-                        FinalStateVia::AzureAsyncOp => { /* no final GET required */ }
-                        FinalStateVia::Location => unreachable!("final-state-via location is not supposed to be handled by the async op poller"),
-                        FinalStateVia::OriginalUri => {
-                            req = Some(Request::new(self.origin_url.clone(), Method::Get));
-                        }
-                        FinalStateVia::OperationLocation => unreachable!("final-state-via operation-location is not supposed to be handled by the async op poller"),
+            Method::Post => match self.final_state {
+                Some(FinalStateVia::AzureAsyncOp) => { /* no final GET required */ }
+                Some(FinalStateVia::OriginalUri) => {
+                    req = Some(Request::new(self.origin_url.clone(), Method::Get));
+                }
+                // Otherwise (e.g. final-state-via location), the final GET is on the Location URL, if any.
+                _ => {
+                    if let Some(ref loc_url) = self.loc_url {
+                        req = Some(Request::new(loc_url.clone(), Method::Get));
                     }
-                } else if let Some(ref loc_url) = self.loc_url {
-                    req = Some(Request::new(loc_url.clone(), Method::Get));
                 }
-            }
+            },
             _ => {}
         }
         if req.is_none() {
@@ -5,8 +5,8 @@ use azure_core::http::{Method, Pipeline, Request, StatusCode, Url};
 use crate::client::poller::utils::{self, get_provisioning_state};
 
 use super::utils::result_helper;
-use super::PollingHandler;
 use super::{utils::LROStatus, Response};
+use super::{HandlerKind, PollingHandler, ResumeToken};
 
 pub struct Poller {
     pl: Pipeline,
@@ -17,6 +17,9 @@ pub struct Poller {
     // The URL for polling.
     poll_url: Url,
 
+    // The HTTP method from the initial LRO request.
+    method: Method,
+
     // The LRO's current state.
     cur_state: LROStatus,
 }
@@ -47,9 +50,32 @@ impl Poller {
             pl,
             resp: resp.clone(),
             poll_url: req.url().clone(),
+            method: req.method(),
             cur_state,
         })
     }
+
+    pub fn from_resume_token(pl: Pipeline, token: &ResumeToken) -> Result<Self> {
+        Ok(Self {
+            pl,
+            resp: super::empty_response(),
+            poll_url: ResumeToken::parse_url(&token.poll_url)?,
+            method: token.method()?,
+            cur_state: LROStatus::InProgress,
+        })
+    }
+
+    pub fn resume_token(&self) -> ResumeToken {
+        ResumeToken {
+            handler: HandlerKind::Body,
+            method: self.method.to_string(),
+            origin_url: self.poll_url.to_string(),
+            poll_url: self.poll_url.to_string(),
+            loc_url: None,
+            final_state: None,
+            result_path: None,
+        }
+    }
 }
 
 impl PollingHandler for Poller {
@@ -99,6 +125,10 @@ impl PollingHandler for Poller {
         self.cur_state.is_terminal()
     }
 
+    fn status(&self) -> LROStatus {
+        self.cur_state
+    }
+
     async fn result(&self, _: &azure_core::http::Context<'_>) -> Result<Response> {
         result_helper(&self.resp, self.cur_state.is_failed(), None)
     }
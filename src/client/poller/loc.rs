@@ -6,8 +6,8 @@ use azure_core::Result;
 use crate::client::poller::utils::{get_provisioning_state, is_non_terminal_http_status_code};
 
 use super::utils::result_helper;
-use super::PollingHandler;
 use super::{utils::LROStatus, Response};
+use super::{HandlerKind, PollingHandler, ResumeToken};
 
 pub struct Poller {
     pl: Pipeline,
@@ -15,14 +15,21 @@ pub struct Poller {
     // The response of the last call (either sync or poll call).
     resp: Response,
 
+    // The URL from Location header.
     loc_url: Url,
 
+    // The URL from the initial LRO request.
+    origin_url: Url,
+
+    // The HTTP method from the initial LRO request.
+    method: Method,
+
     // The LRO's current state.
     cur_state: LROStatus,
 }
 
 impl Poller {
-    pub fn new(pl: Pipeline, resp: Response) -> Result<Self> {
+    pub fn new(pl: Pipeline, req: &Request, resp: Response) -> Result<Self> {
         let loc_url = resp.headers.get_as(&LOCATION).map_err(|err| {
             err.context(format!("parsing header `{}` as a URL", LOCATION.as_str()))
         })?;
@@ -31,9 +38,34 @@ impl Poller {
             pl,
             resp,
             loc_url,
+            origin_url: req.url().clone(),
+            method: req.method(),
             cur_state,
         })
     }
+
+    pub fn from_resume_token(pl: Pipeline, token: &ResumeToken) -> Result<Self> {
+        Ok(Self {
+            pl,
+            resp: super::empty_response(),
+            loc_url: ResumeToken::parse_url(&token.poll_url)?,
+            origin_url: ResumeToken::parse_url(&token.origin_url)?,
+            method: token.method()?,
+            cur_state: LROStatus::InProgress,
+        })
+    }
+
+    pub fn resume_token(&self) -> ResumeToken {
+        ResumeToken {
+            handler: HandlerKind::Loc,
+            method: self.method.to_string(),
+            origin_url: self.origin_url.to_string(),
+            poll_url: self.loc_url.to_string(),
+            loc_url: None,
+            final_state: None,
+            result_path: None,
+        }
+    }
 }
 
 impl PollingHandler for Poller {
@@ -80,6 +112,10 @@ impl PollingHandler for Poller {
         self.cur_state.is_terminal()
     }
 
+    fn status(&self) -> LROStatus {
+        self.cur_state
+    }
+
     async fn result(&self, _: &azure_core::http::Context<'_>) -> Result<Response> {
         result_helper(&self.resp, self.cur_state.is_failed(), None)
     }
@@ -1,4 +1,4 @@
-use super::{PollingHandler, Response};
+use super::{utils::LROStatus, PollingHandler, Response};
 use azure_core::{
     http::{Context, Request},
     Result,
@@ -30,4 +30,8 @@ impl PollingHandler for Poller {
     fn done(&self) -> bool {
         true
     }
+
+    fn status(&self) -> LROStatus {
+        LROStatus::Succeeded
+    }
 }
@@ -1,8 +1,8 @@
 use crate::client::poller::utils::{self, get_resource_location, result_helper};
 
 use super::utils::{get_lro_status, get_provisioning_state};
-use super::PollingHandler;
 use super::{utils::LROStatus, FinalStateVia, Response};
+use super::{HandlerKind, PollingHandler, ResumeToken};
 use azure_core::error::ErrorKind;
 use azure_core::http::headers::{HeaderName, LOCATION};
 use azure_core::http::{Method, Pipeline, Request, Url};
@@ -68,6 +68,36 @@ impl Poller {
             cur_state,
         })
     }
+
+    pub fn from_resume_token(pl: Pipeline, token: &ResumeToken) -> Result<Self> {
+        Ok(Self {
+            pl,
+            resp: super::empty_response(),
+            op_loc_url: ResumeToken::parse_url(&token.poll_url)?,
+            loc_url: token
+                .loc_url
+                .as_deref()
+                .map(ResumeToken::parse_url)
+                .transpose()?,
+            origin_url: ResumeToken::parse_url(&token.origin_url)?,
+            method: token.method()?,
+            final_state: token.final_state,
+            result_path: token.result_path.clone(),
+            cur_state: LROStatus::InProgress,
+        })
+    }
+
+    pub fn resume_token(&self) -> ResumeToken {
+        ResumeToken {
+            handler: HandlerKind::Op,
+            method: self.method.to_string(),
+            origin_url: self.origin_url.to_string(),
+            poll_url: self.op_loc_url.to_string(),
+            loc_url: self.loc_url.as_ref().map(|url| url.to_string()),
+            final_state: self.final_state,
+            result_path: self.result_path.clone(),
+        }
+    }
 }
 
 impl PollingHandler for Poller {
@@ -103,6 +133,10 @@ impl PollingHandler for Poller {
         self.cur_state.is_terminal()
     }
 
+    fn status(&self) -> LROStatus {
+        self.cur_state
+    }
+
     async fn result(&self, ctx: &azure_core::http::Context<'_>) -> Result<Response> {
         assert!(self.cur_state.is_terminal());
 
@@ -111,10 +145,8 @@ impl PollingHandler for Poller {
         }
 
         let mut req: Option<Request> = None;
-        if let Some(FinalStateVia::Location) = self.final_state {
-            if let Some(loc_url) = &self.loc_url {
-                req = Some(Request::new(loc_url.clone(), Method::Get));
-            }
+        if let (Some(FinalStateVia::Location), Some(loc_url)) = (self.final_state, &self.loc_url) {
+            req = Some(Request::new(loc_url.clone(), Method::Get));
         } else if let Some(rl) = get_resource_location(&self.resp)? {
             req = Some(Request::new(rl, Method::Get));
         } else {
@@ -132,7 +164,8 @@ impl PollingHandler for Poller {
         }
 
         if req.is_none() {
-            return Ok(self.resp.clone());
+            // No final GET, the result (if any) is in the last response.
+            return result_helper(&self.resp, false, self.result_path.as_deref());
         }
         let mut req = req.unwrap();
 
@@ -9,15 +9,19 @@ use azure_core::{
     http::{headers::HeaderName, StatusCode, Url},
     Error, Result,
 };
+use serde::{Deserialize, Serialize};
 use serde_json::{from_slice, Value};
 use std::collections::HashMap;
 
-#[allow(dead_code)]
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FinalStateVia {
+    #[serde(rename = "azure-async-operation")]
     AzureAsyncOp,
+    #[serde(rename = "location")]
     Location,
+    #[serde(rename = "original-uri")]
     OriginalUri,
+    #[serde(rename = "operation-location")]
     OperationLocation,
 }
 
@@ -0,0 +1,194 @@
+use std::env;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+pub const CLOUD_ENV: &str = "AZURE_CLOUD";
+pub const ARM_ENDPOINT_ENV: &str = "AZURE_ARM_ENDPOINT";
+pub const ARM_AUDIENCE_ENV: &str = "AZURE_ARM_AUDIENCE";
+pub const AUTHORITY_HOST_ENV: &str = "AZURE_AUTHORITY_HOST";
+
+pub const AZURE_CLOUD: &str = "AzureCloud";
+pub const AZURE_CHINA_CLOUD: &str = "AzureChinaCloud";
+pub const AZURE_US_GOVERNMENT: &str = "AzureUSGovernment";
+
+// Cloud is the profile of an Azure cloud.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cloud {
+    pub name: String,
+
+    // The endpoint of the Azure Resource Manager.
+    pub arm_endpoint: String,
+
+    // The audience of the token used to access the Azure Resource Manager.
+    pub audience: String,
+
+    // The Microsoft Entra authority host.
+    pub authority_host: String,
+}
+
+// CloudSpec is the spec of a custom cloud defined in the config file.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CloudSpec {
+    pub arm_endpoint: String,
+
+    // Defaults to the arm_endpoint.
+    pub audience: Option<String>,
+
+    // Defaults to the authority host of the AzureCloud.
+    pub authority_host: Option<String>,
+}
+
+impl Cloud {
+    pub fn azure_cloud() -> Self {
+        Self {
+            name: AZURE_CLOUD.to_string(),
+            arm_endpoint: "https://management.azure.com".to_string(),
+            audience: "https://management.azure.com".to_string(),
+            authority_host: "https://login.microsoftonline.com".to_string(),
+        }
+    }
+
+    pub fn azure_china_cloud() -> Self {
+        Self {
+            name: AZURE_CHINA_CLOUD.to_string(),
+            arm_endpoint: "https://management.chinacloudapi.cn".to_string(),
+            audience: "https://management.chinacloudapi.cn".to_string(),
+            authority_host: "https://login.chinacloudapi.cn".to_string(),
+        }
+    }
+
+    pub fn azure_us_government() -> Self {
+        Self {
+            name: AZURE_US_GOVERNMENT.to_string(),
+            arm_endpoint: "https://management.usgovcloudapi.net".to_string(),
+            audience: "https://management.usgovcloudapi.net".to_string(),
+            authority_host: "https://login.microsoftonline.us".to_string(),
+        }
+    }
+
+    pub fn well_known() -> Vec<Self> {
+        vec![
+            Self::azure_cloud(),
+            Self::azure_china_cloud(),
+            Self::azure_us_government(),
+        ]
+    }
+
+    fn from_spec(name: &str, spec: &CloudSpec) -> Self {
+        Self {
+            name: name.to_string(),
+            arm_endpoint: spec.arm_endpoint.clone(),
+            audience: spec.audience.clone().unwrap_or(spec.arm_endpoint.clone()),
+            authority_host: spec
+                .authority_host
+                .clone()
+                .unwrap_or(Self::azure_cloud().authority_host),
+        }
+    }
+
+    // resolve resolves the cloud to use, by the name from (in order):
+    // 1. The "--cloud" option
+    // 2. The AZURE_CLOUD environment variable
+    // 3. The "cloud" in the config file
+    // The name defaults to "AzureCloud". Then the endpoints of the resolved cloud can be
+    // overridden by the AZURE_ARM_ENDPOINT, AZURE_ARM_AUDIENCE and AZURE_AUTHORITY_HOST
+    // environment variables, e.g. to point to a local ARM stand-in.
+    pub fn resolve(name: Option<&str>, config: &Config) -> Result<Self> {
+        let env_name = env::var(CLOUD_ENV).ok();
+        let name = name.or(env_name.as_deref()).or(config.cloud.as_deref());
+        let mut cloud = Self::select(name, config)?;
+        if let Ok(v) = env::var(ARM_ENDPOINT_ENV) {
+            cloud.arm_endpoint = v;
+        }
+        if let Ok(v) = env::var(ARM_AUDIENCE_ENV) {
+            cloud.audience = v;
+        }
+        if let Ok(v) = env::var(AUTHORITY_HOST_ENV) {
+            cloud.authority_host = v;
+        }
+        Ok(cloud)
+    }
+
+    // select selects the cloud by name from the well known clouds and the custom clouds.
+    fn select(name: Option<&str>, config: &Config) -> Result<Self> {
+        let Some(name) = name else {
+            return Ok(Self::azure_cloud());
+        };
+        if let Some(cloud) = Self::well_known()
+            .into_iter()
+            .find(|cloud| cloud.name.eq_ignore_ascii_case(name))
+        {
+            return Ok(cloud);
+        }
+        if let Some(spec) = config.clouds.get(name) {
+            return Ok(Self::from_spec(name, spec));
+        }
+        bail!(
+            r#"unknown cloud "{name}", expect one of the well known clouds ({}) or a custom cloud defined in the config file"#,
+            Self::well_known()
+                .iter()
+                .map(|cloud| cloud.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    // scope returns the scope of the token used to access the Azure Resource Manager.
+    pub fn scope(&self) -> String {
+        format!("{}/.default", self.audience.trim_end_matches('/'))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn select() {
+        let config = Config {
+            clouds: BTreeMap::from([(
+                "local".to_string(),
+                CloudSpec {
+                    arm_endpoint: "http://localhost:8080".to_string(),
+                    audience: None,
+                    authority_host: None,
+                },
+            )]),
+            ..Config::default()
+        };
+        assert_eq!(Cloud::select(None, &config).unwrap(), Cloud::azure_cloud());
+        assert_eq!(
+            Cloud::select(Some("azurechinacloud"), &config).unwrap(),
+            Cloud::azure_china_cloud()
+        );
+        assert_eq!(
+            Cloud::select(Some("local"), &config).unwrap(),
+            Cloud {
+                name: "local".to_string(),
+                arm_endpoint: "http://localhost:8080".to_string(),
+                audience: "http://localhost:8080".to_string(),
+                authority_host: "https://login.microsoftonline.com".to_string(),
+            }
+        );
+        assert!(Cloud::select(Some("foo"), &config).is_err());
+    }
+
+    #[test]
+    fn scope() {
+        assert_eq!(
+            Cloud::azure_cloud().scope(),
+            "https://management.azure.com/.default"
+        );
+        let mut cloud = Cloud::azure_us_government();
+        cloud.audience = "https://management.core.usgovcloudapi.net/".to_string();
+        assert_eq!(
+            cloud.scope(),
+            "https://management.core.usgovcloudapi.net/.default"
+        );
+    }
+}
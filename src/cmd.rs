@@ -1,15 +1,43 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
+use crate::api::arg_type::ArgType;
 use crate::api::cli_expander::Shell;
 use crate::api::metadata_command::Method;
-use crate::api::{metadata_command, metadata_index, ApiManager};
+use crate::api::output::OutputFormat;
+use crate::api::{metadata_command, metadata_index, query, ApiManager};
 use crate::arg::CliInput;
+use crate::auth::{self, AuthMode};
+use crate::client::poller::{PollUntilDoneOptions, ProgressFunc};
+use crate::cloud::{self, Cloud};
+use crate::config::{self, Config};
 use anyhow::{bail, Result};
 use clap::builder::PossibleValuesParser;
-use clap::{command, Arg, Command};
+use clap::{command, Arg, ArgMatches, Command};
 
 pub const ID_OPTION: &str = "id";
 pub const STDIN_OPTION: &str = "stdin";
+pub const MAX_ITEMS_OPTION: &str = "max-items";
+pub const PAGE_SIZE_OPTION: &str = "page-size";
+pub const STREAM_PAGES_OPTION: &str = "stream-pages";
+pub const QUERY_OPTION: &str = "query";
+pub const OUTPUT_OPTION: &str = "output";
+pub const CLOUD_OPTION: &str = "cloud";
+pub const AUTH_MODE_OPTION: &str = "auth-mode";
+pub const AUX_TENANTS_OPTION: &str = "aux-tenants";
+pub const DEBUG_OPTION: &str = "debug";
+pub const HEADER_OPTION: &str = "header";
+pub const NO_WAIT_OPTION: &str = "no-wait";
+pub const POLLING_INTERVAL_OPTION: &str = "polling-interval";
+pub const TIMEOUT_OPTION: &str = "timeout";
+pub const SKIP_VALIDATION_OPTION: &str = "skip-validation";
+pub const SET_OPTION: &str = "set";
+pub const ADD_OPTION: &str = "add";
+pub const REMOVE_OPTION: &str = "remove";
+pub const GENERATE_SKELETON_OPTION: &str = "generate-skeleton";
+pub const FILE_FORMAT_OPTION: &str = "file-format";
+pub const VAR_OPTION: &str = "var";
+pub const VAR_FILE_OPTION: &str = "var-file";
 
 #[derive(Debug)]
 pub struct ResourceId(String);
@@ -63,15 +91,111 @@ impl ResourceId {
 pub fn cmd() -> Command {
     cmd_base().subcommands([
         Command::new("lsp").about("Start the LSP server."),
+        cmd_config(),
+        cmd_lro(),
         cmd_api_stub(),
     ])
 }
 
+fn cmd_lro() -> Command {
+    let token = Arg::new("token")
+        .required(true)
+        .help(r#"The resume token output by the "--no-wait" API command"#);
+    Command::new("lro")
+        .about(r#"Track the long running operation started with "--no-wait"."#)
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommands([
+            Command::new("wait")
+                .about("Wait for the long running operation to finish, and output its result.")
+                .arg(token.clone())
+                .args(build_poll_args()),
+            Command::new("show")
+                .about("Poll the long running operation once, and output its current status.")
+                .arg(token),
+        ])
+}
+
+fn cmd_config() -> Command {
+    let key = Arg::new("key")
+        .value_parser(PossibleValuesParser::new(config::KEYS.iter().copied()))
+        .help("The config key");
+    Command::new("config")
+        .about("Manage the persistent CLI configuration.")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommands([
+            Command::new("set")
+                .about("Set a config value.")
+                .arg(key.clone().required(true))
+                .arg(Arg::new("value").required(true).help("The config value")),
+            Command::new("get")
+                .about("Get a config value, or the whole config if no key is specified.")
+                .arg(key.clone()),
+            Command::new("unset")
+                .about("Unset a config value.")
+                .arg(key.required(true)),
+        ])
+}
+
 fn cmd_base() -> Command {
     command!()
         .subcommand_required(true)
         .arg_required_else_help(true)
         .disable_help_subcommand(true)
+        .arg(
+            Arg::new(CLOUD_OPTION)
+                .long(CLOUD_OPTION)
+                .value_name("NAME")
+                .global(true)
+                .help(format!(
+                    r#"The cloud to use, either one of the well known clouds ({}) or a custom cloud defined in the config file. Defaults to the "{}" environment variable, or the "cloud" in the config file"#,
+                    Cloud::well_known()
+                        .iter()
+                        .map(|cloud| cloud.name.clone())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    cloud::CLOUD_ENV,
+                )),
+        )
+        .arg(
+            Arg::new(AUTH_MODE_OPTION)
+                .long(AUTH_MODE_OPTION)
+                .value_name("MODE")
+                .global(true)
+                .value_parser(PossibleValuesParser::new(AuthMode::all().map(|mode| mode.name())))
+                .help(format!(
+                    r#"The credential to authenticate with. Defaults to the "{}" environment variable, or the "auth.mode" in the config file, or "default" otherwise"#,
+                    auth::AUTH_MODE_ENV,
+                )),
+        )
+        .arg(
+            Arg::new(AUX_TENANTS_OPTION)
+                .long(AUX_TENANTS_OPTION)
+                .value_name("TENANTS")
+                .global(true)
+                .value_delimiter(',')
+                .action(clap::ArgAction::Append)
+                .help(format!(
+                    r#"The comma separated auxiliary tenants (at most {}), whose tokens are sent along for the cross tenant operations. Defaults to the "auth.aux_tenants" in the config file"#,
+                    auth::MAX_AUX_TENANTS,
+                )),
+        )
+        .arg(
+            Arg::new(DEBUG_OPTION)
+                .long(DEBUG_OPTION)
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Log the HTTP requests and responses to the stderr, with the secrets redacted"),
+        )
+        .arg(
+            Arg::new(HEADER_OPTION)
+                .long(HEADER_OPTION)
+                .value_name("KEY=VALUE")
+                .global(true)
+                .action(clap::ArgAction::Append)
+                .help("Extra header sent along with every request"),
+        )
 }
 
 fn cmd_api_stub() -> Command {
@@ -96,7 +220,7 @@ pub fn cmd_api_base() -> Command {
 
 // cmd_api parses the raw CLI args for `api` subcommand, returns a precise clap::Command and
 // a potential Command metadata (if the raw CLI args ends to a command).
-pub fn cmd_api(api_manager: &ApiManager, input: &CliInput) -> Command {
+pub fn cmd_api(api_manager: &ApiManager, input: &CliInput, config: &Config) -> Command {
     let pos_args = input.pos_args();
 
     // No positional argument specified, list the rps
@@ -186,7 +310,7 @@ pub fn cmd_api(api_manager: &ApiManager, input: &CliInput) -> Command {
                 match api_manager.index.locate_command_file(input) {
                     Ok(command_file) => match api_manager.read_command(&command_file) {
                         Ok(command) => {
-                            cmd = cmd.args(build_args(&c.versions, &command));
+                            cmd = cmd.args(build_args(&c.versions, &command, config));
                         }
                         Err(err) => {
                             tracing::error!("read command failed: {err}");
@@ -235,7 +359,11 @@ pub fn cmd_api(api_manager: &ApiManager, input: &CliInput) -> Command {
     cmd
 }
 
-fn build_args(versions: &Vec<String>, command: &metadata_command::Command) -> Vec<Arg> {
+fn build_args(
+    versions: &Vec<String>,
+    command: &metadata_command::Command,
+    config: &Config,
+) -> Vec<Arg> {
     let mut out = vec![];
 
     // Build the api-version option
@@ -249,6 +377,8 @@ fn build_args(versions: &Vec<String>, command: &metadata_command::Command) -> Ve
             .value_parser(PossibleValuesParser::new(versions)),
     );
 
+    let has_body = command.contains_request_body() || command.is_updatable();
+
     // Build the (required) ID options
 
     if let Some(default_ag) = command.arg_groups.iter().find(|ag| ag.name == "") {
@@ -258,7 +388,7 @@ fn build_args(versions: &Vec<String>, command: &metadata_command::Command) -> Ve
             .args
             .iter()
             .filter(|arg| !arg.hide.unwrap_or(false))
-            .map(|arg| build_id_arg(arg))
+            .map(|arg| build_id_arg(arg, config, has_body))
             .collect::<Vec<_>>();
         out.extend(default_args);
 
@@ -285,13 +415,14 @@ fn build_args(versions: &Vec<String>, command: &metadata_command::Command) -> Ve
                 .long(STDIN_OPTION)
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with(ID_OPTION)
-                .help(format!(r#"Reading the resource id and request payload (only for "create" commands) from stdin as one or multiple compact JSON objects. This conflicts with {conflicts:?}"#))
+                .help(format!(r#"Reading the resource id and request payload (only for "create" commands) or the current resource (only for the generic update commands) from stdin as one or multiple compact JSON objects. This conflicts with {conflicts:?}"#))
         );
     }
 
     // Build the payload related options
-    let is_put = command.operations.iter().any(|op| op.is_put());
-    if command.contains_request_body() {
+    // The generic update builds its payload from the current resource, rather than from scratch.
+    let is_put = command.operations.iter().any(|op| op.is_put()) && !command.is_updatable();
+    if has_body {
         let mut file_conflicts = vec!["edit"];
         if is_put {
             file_conflicts.push(STDIN_OPTION);
@@ -303,7 +434,30 @@ fn build_args(versions: &Vec<String>, command: &metadata_command::Command) -> Ve
                 .value_name("PATH")
                 .value_parser(clap::value_parser!(std::path::PathBuf))
                 .conflicts_with_all(file_conflicts)
-                .help("Read request payload from the file"),
+                .help(r#"Read request payload from the file (in JSON, YAML or HCL), or from stdin if it is "-""#),
+        );
+        out.push(
+            Arg::new(FILE_FORMAT_OPTION)
+                .long(FILE_FORMAT_OPTION)
+                .value_name("FORMAT")
+                .value_parser(["json", "yaml", "hcl"])
+                .requires("file")
+                .help(r#"Format of the request payload file, which is detected by the file extension by default, or by the content for stdin"#),
+        );
+        out.push(
+            Arg::new(VAR_OPTION)
+                .long(VAR_OPTION)
+                .value_name("KEY=VALUE")
+                .action(clap::ArgAction::Append)
+                .help(r#"Variable referenced as "var.<KEY>" in the HCL request payload, whose value is in JSON or a string"#),
+        );
+        out.push(
+            Arg::new(VAR_FILE_OPTION)
+                .long(VAR_FILE_OPTION)
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .action(clap::ArgAction::Append)
+                .help(r#"HCL file of the variables (e.g. "name = "foo""), which are overridden by "--var""#),
         );
         out.push(
             Arg::new("edit")
@@ -311,7 +465,20 @@ fn build_args(versions: &Vec<String>, command: &metadata_command::Command) -> Ve
                 .short('e')
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with_all(["file", STDIN_OPTION])
-                .help("Open default editor to compose request payload"),
+                .help("Open default editor to compose request payload, or to edit the live resource (as HCL) for the update commands, whose changes are confirmed from the terminal"),
+        );
+        out.push(
+            Arg::new(GENERATE_SKELETON_OPTION)
+                .long(GENERATE_SKELETON_OPTION)
+                .value_name("FORMAT")
+                .value_parser(["hcl", "json"])
+                .help(r#"Output the request payload skeleton in the format, which can be used by "--file""#),
+        );
+        out.push(
+            Arg::new(SKIP_VALIDATION_OPTION)
+                .long(SKIP_VALIDATION_OPTION)
+                .action(clap::ArgAction::SetTrue)
+                .help("Skip the client-side validation of the request payload against its schema"),
         );
     }
 
@@ -322,26 +489,151 @@ fn build_args(versions: &Vec<String>, command: &metadata_command::Command) -> Ve
                 .value_parser(PossibleValuesParser::new(Shell::variants()))
                 .help(r#"Print the equivalent CLI command instead of executing it, useful when combined with "--file" or "--edit""#),
         );
+    out.push(
+        Arg::new(QUERY_OPTION)
+            .long(QUERY_OPTION)
+            .value_name("JMESPATH")
+            .value_parser(query::validate)
+            .help("JMESPath query string to filter the response, see http://jmespath.org/ for more information and examples"),
+    );
+    let mut output = Arg::new(OUTPUT_OPTION)
+        .long(OUTPUT_OPTION)
+        .value_name("FORMAT")
+        .value_parser(PossibleValuesParser::new(OutputFormat::variants()))
+        .help("Output format, the response is output as is if not specified");
+    // Only use the short option when it doesn't conflict with the command's own arguments.
+    if !command
+        .arg_groups
+        .iter()
+        .flat_map(|ag| &ag.args)
+        .any(|arg| arg.options.iter().any(|opt| opt == "o"))
+    {
+        output = output.short('o');
+    }
+    out.push(output);
+
+    // Build the pagination related options
+    if command.is_pageable() {
+        out.push(
+            Arg::new(MAX_ITEMS_OPTION)
+                .long(MAX_ITEMS_OPTION)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Maximum number of items to return in total, across all the pages"),
+        );
+        out.push(
+            Arg::new(PAGE_SIZE_OPTION)
+                .long(PAGE_SIZE_OPTION)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help(
+                    r#"Number of items to request per page (via "$top"), if supported by the API"#,
+                ),
+        );
+        out.push(
+            Arg::new(STREAM_PAGES_OPTION)
+                .long(STREAM_PAGES_OPTION)
+                .action(clap::ArgAction::SetTrue)
+                .help("Output each page as it arrives, instead of merging all the pages into one"),
+        );
+    }
+
+    // Build the LRO related options
+    if command.may_be_lro() {
+        out.push(
+            Arg::new(NO_WAIT_OPTION)
+                .long(NO_WAIT_OPTION)
+                .action(clap::ArgAction::SetTrue)
+                .help(r#"Do not wait for the long running operation to finish, but output its status and a resume token, which can be used by "lro wait" or "lro show""#),
+        );
+        out.extend(
+            build_poll_args()
+                .into_iter()
+                .map(|arg| arg.conflicts_with(NO_WAIT_OPTION)),
+        );
+    }
+
+    // Build the generic update options
+    if command.is_updatable() {
+        out.push(
+            Arg::new(SET_OPTION)
+                .long(SET_OPTION)
+                .value_name("PATH=VALUE")
+                .action(clap::ArgAction::Append)
+                .help(r#"Set the value at the JSON path of the resource, e.g. "properties.x=1""#),
+        );
+        out.push(
+            Arg::new(ADD_OPTION)
+                .long(ADD_OPTION)
+                .value_names(["PATH", "VALUE"])
+                .num_args(2)
+                .action(clap::ArgAction::Append)
+                .help(r#"Add the value to the array, or the key/value pairs to the object, at the JSON path of the resource, e.g. "tags k=v""#),
+        );
+        out.push(
+            Arg::new(REMOVE_OPTION)
+                .long(REMOVE_OPTION)
+                .value_name("PATH")
+                .action(clap::ArgAction::Append)
+                .help(r#"Remove the property or the array item at the JSON path of the resource, e.g. "properties.y" or "properties.list[0]""#),
+        );
+    }
 
-    // Build the remaining optional arguments based on the command metadata.
-    // NOTE: Only the top level arg groups are exposed.
+    // Build the remaining optional arguments based on the command metadata, including the nested
+    // ones, grouped by the arg group names in the help message.
     command
         .arg_groups
         .iter()
         .filter(|arg| arg.name != "")
         .for_each(|ag| {
             out.extend(
-                ag.args
+                ag.all_args()
                     .iter()
                     .filter(|arg| !arg.hide.unwrap_or(false))
-                    .map(|arg| build_body_arg(arg, is_put)),
+                    .map(|arg| build_body_arg(arg, is_put, config).help_heading(ag.name.clone())),
             )
         });
 
     out
 }
 
-fn build_id_arg(arg: &metadata_command::Arg) -> Arg {
+// build_poll_args builds the options controlling how to wait for the long running operation.
+fn build_poll_args() -> Vec<Arg> {
+    vec![
+        Arg::new(POLLING_INTERVAL_OPTION)
+            .long(POLLING_INTERVAL_OPTION)
+            .value_name("SECONDS")
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .help(r#"Interval between polls of the long running operation, in absence of the "Retry-After" header (default: 30)"#),
+        Arg::new(TIMEOUT_OPTION)
+            .long(TIMEOUT_OPTION)
+            .value_name("SECONDS")
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .help("Maximum time to wait for the long running operation to finish"),
+    ]
+}
+
+// poll_until_done_options builds the options to wait for the long running operation from the
+// command line options.
+pub fn poll_until_done_options(
+    matches: &ArgMatches,
+    progress: Option<ProgressFunc>,
+) -> PollUntilDoneOptions {
+    let seconds = |id: &str| {
+        matches
+            .try_get_one::<u64>(id)
+            .ok()
+            .flatten()
+            .map(|v| Duration::from_secs(*v))
+    };
+    PollUntilDoneOptions {
+        frequency: seconds(POLLING_INTERVAL_OPTION),
+        timeout: seconds(TIMEOUT_OPTION),
+        progress,
+    }
+}
+
+fn build_id_arg(arg: &metadata_command::Arg, config: &Config, has_body: bool) -> Arg {
     let mut out = build_arg(arg);
 
     // Id related args conflicts with --id and --stdin
@@ -361,19 +653,47 @@ fn build_id_arg(arg: &metadata_command::Arg) -> Arg {
     // Update conflicts
     out = out.conflicts_with_all(other_id_args);
 
-    // Id related args are required
-    if let Some(required) = arg.required {
+    // Id related args are required, unless there is a default value configured.
+    // The default value is not set to the arg, but applied when the operation is invoked, so that
+    // it neither affects the operation selection nor applies with "--id" or "--stdin".
+    if let Some(default) = arg
+        .id_part
+        .as_ref()
+        .and_then(|id_part| config.default_for_id_part(id_part))
+    {
+        let mut help = out
+            .get_help()
+            .map(|help| help.to_string())
+            .unwrap_or_default();
+        if !help.is_empty() {
+            help += " ";
+        }
+        help += format!(r#"Defaults to "{default}" in the config"#).as_str();
+        out = out.help(help);
+    } else if let Some(required) = arg.required {
         if required {
-            out = out.required_unless_present_any(other_id_args);
+            // The request payload skeleton can be generated without the id.
+            let mut required_unless = other_id_args.to_vec();
+            if has_body {
+                required_unless.push(GENERATE_SKELETON_OPTION);
+            }
+            out = out.required_unless_present_any(required_unless);
         }
     }
 
     out
 }
 
-fn build_body_arg(arg: &metadata_command::Arg, is_put: bool) -> Arg {
+fn build_body_arg(arg: &metadata_command::Arg, is_put: bool, config: &Config) -> Arg {
     let mut out = build_arg(arg);
 
+    // Only apply the default location for creation, to avoid unexpected changes during update.
+    if is_put && arg.type_ == "ResourceLocation" {
+        if let Some(location) = config.defaults.location.as_ref() {
+            out = out.default_value(location.clone());
+        }
+    }
+
     if is_put {
         // PUT operation with --stdin specified will read the body from stdin.
         // Hence all body arguments conflicts with --stdin.
@@ -405,10 +725,16 @@ fn build_arg(arg: &metadata_command::Arg) -> Arg {
             }
         }
     });
+    let arg_type = ArgType::from_metadata(&arg.type_);
     let mut out = Arg::new(arg.var.clone())
-        .value_name("value")
+        .value_name(arg_type.value_name())
+        .value_parser(move |v: &str| arg_type.validate(v))
         .visible_short_aliases(short_aliases)
         .visible_aliases(long_aliases);
+    if arg_type == ArgType::Boolean {
+        // Boolean arguments can be used as a flag, i.e. "--enable" means "--enable true".
+        out = out.num_args(0..=1).default_missing_value("true");
+    }
     if let Some(short) = short {
         out = out.short(short);
     }
@@ -431,3 +757,88 @@ fn build_arg(arg: &metadata_command::Arg) -> Arg {
 fn verify_cmd() {
     cmd().debug_assert();
 }
+
+#[test]
+fn default_resource_group() {
+    let command: metadata_command::Command = serde_json::from_str(
+        r#"
+{
+  "argGroups": [
+    {
+      "name": "",
+      "args": [
+        {"type": "ResourceGroupName", "var": "$Path.resourceGroupName", "options": ["g", "resource-group"], "idPart": "resource_group"},
+        {"type": "SubscriptionId", "var": "$Path.subscriptionId", "options": ["subscription"], "required": true, "idPart": "subscription"}
+      ]
+    }
+  ],
+  "conditions": [
+    {
+      "operator": {
+        "operators": [
+          {"arg": "$Path.subscriptionId", "type": "hasValue"},
+          {"operator": {"arg": "$Path.resourceGroupName", "type": "hasValue"}, "type": "not"}
+        ],
+        "type": "and"
+      },
+      "var": "$Condition_VirtualNetworks_ListAll"
+    },
+    {
+      "operator": {
+        "operators": [
+          {"arg": "$Path.resourceGroupName", "type": "hasValue"},
+          {"arg": "$Path.subscriptionId", "type": "hasValue"}
+        ],
+        "type": "and"
+      },
+      "var": "$Condition_VirtualNetworks_List"
+    }
+  ],
+  "operations": [
+    {"operationId": "VirtualNetworks_ListAll", "when": ["$Condition_VirtualNetworks_ListAll"]},
+    {"operationId": "VirtualNetworks_List", "when": ["$Condition_VirtualNetworks_List"]}
+  ],
+  "resources": []
+}
+"#,
+    )
+    .unwrap();
+    let config = Config {
+        defaults: config::Defaults {
+            subscription: Some("sub".to_string()),
+            resource_group: Some("rg".to_string()),
+            location: None,
+        },
+        ..Config::default()
+    };
+    let defaults = command.id_defaults(&config);
+    let cmd = Command::new("list").args(build_args(
+        &vec!["2024-05-01".to_string()],
+        &command,
+        &config,
+    ));
+    let select = |args: Vec<&str>| {
+        let matches = cmd.clone().get_matches_from(args);
+        let cond = command.build_condition(metadata_command::ConditionOpt::new(
+            None,
+            command.name_args(&matches, &defaults),
+        ));
+        command
+            .select_operation_by_cond(cond.as_ref())
+            .and_then(|op| op.operation_id.clone())
+    };
+
+    // The default resource group doesn't make the list resource group scoped.
+    assert_eq!(
+        select(vec!["list"]),
+        Some("VirtualNetworks_ListAll".to_string())
+    );
+    assert_eq!(
+        select(vec!["list", "-g", "foo"]),
+        Some("VirtualNetworks_List".to_string())
+    );
+    assert_eq!(
+        defaults.get("$Path.resourceGroupName"),
+        Some(&"rg".to_string())
+    );
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AuthSpec;
+use crate::cloud::CloudSpec;
+
+pub const CONFIG_DIR_ENV: &str = "AZ_RS_CONFIG_DIR";
+
+// The keys that can be managed by the "config set/get/unset" commands.
+pub const KEY_CLOUD: &str = "cloud";
+pub const KEY_DEFAULTS_SUBSCRIPTION: &str = "defaults.subscription";
+pub const KEY_DEFAULTS_RESOURCE_GROUP: &str = "defaults.resource_group";
+pub const KEY_DEFAULTS_LOCATION: &str = "defaults.location";
+pub const KEY_AUTH_MODE: &str = "auth.mode";
+pub const KEY_AUTH_TENANT_ID: &str = "auth.tenant_id";
+pub const KEY_AUTH_CLIENT_ID: &str = "auth.client_id";
+pub const KEY_AUTH_CLIENT_CERTIFICATE: &str = "auth.client_certificate";
+pub const KEY_AUTH_FEDERATED_TOKEN_FILE: &str = "auth.federated_token_file";
+pub const KEY_AUTH_TOKEN_FILE: &str = "auth.token_file";
+pub const KEY_AUTH_AUX_TENANTS: &str = "auth.aux_tenants";
+pub const KEYS: &[&str] = &[
+    KEY_CLOUD,
+    KEY_DEFAULTS_SUBSCRIPTION,
+    KEY_DEFAULTS_RESOURCE_GROUP,
+    KEY_DEFAULTS_LOCATION,
+    KEY_AUTH_MODE,
+    KEY_AUTH_TENANT_ID,
+    KEY_AUTH_CLIENT_ID,
+    KEY_AUTH_CLIENT_CERTIFICATE,
+    KEY_AUTH_FEDERATED_TOKEN_FILE,
+    KEY_AUTH_TOKEN_FILE,
+    KEY_AUTH_AUX_TENANTS,
+];
+
+// Config is the persistent CLI configuration, which is read from "~/.config/az-rs/config.toml"
+// (or "$AZ_RS_CONFIG_DIR/config.toml").
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Config {
+    // The name of the cloud to use, either a well known cloud or one of the custom clouds.
+    pub cloud: Option<String>,
+
+    // The default values of the command arguments.
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    pub defaults: Defaults,
+
+    // The authentication settings.
+    #[serde(default, skip_serializing_if = "AuthSpec::is_empty")]
+    pub auth: AuthSpec,
+
+    // The custom clouds, keyed by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub clouds: BTreeMap<String, CloudSpec>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Defaults {
+    pub subscription: Option<String>,
+    pub resource_group: Option<String>,
+    pub location: Option<String>,
+}
+
+impl Defaults {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Config {
+    pub fn from_toml(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    fn field_mut(&mut self, key: &str) -> Result<&mut Option<String>> {
+        match key {
+            KEY_CLOUD => Ok(&mut self.cloud),
+            KEY_DEFAULTS_SUBSCRIPTION => Ok(&mut self.defaults.subscription),
+            KEY_DEFAULTS_RESOURCE_GROUP => Ok(&mut self.defaults.resource_group),
+            KEY_DEFAULTS_LOCATION => Ok(&mut self.defaults.location),
+            KEY_AUTH_MODE => Ok(&mut self.auth.mode),
+            KEY_AUTH_TENANT_ID => Ok(&mut self.auth.tenant_id),
+            KEY_AUTH_CLIENT_ID => Ok(&mut self.auth.client_id),
+            KEY_AUTH_CLIENT_CERTIFICATE => Ok(&mut self.auth.client_certificate),
+            KEY_AUTH_FEDERATED_TOKEN_FILE => Ok(&mut self.auth.federated_token_file),
+            KEY_AUTH_TOKEN_FILE => Ok(&mut self.auth.token_file),
+            KEY_AUTH_AUX_TENANTS => Ok(&mut self.auth.aux_tenants),
+            _ => bail!("unknown config key {key:?}, expect one of {KEYS:?}"),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.clone().field_mut(key)?.clone())
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        *self.field_mut(key)? = Some(value.to_string());
+        Ok(())
+    }
+
+    pub fn unset(&mut self, key: &str) -> Result<()> {
+        *self.field_mut(key)? = None;
+        Ok(())
+    }
+
+    // default_for_id_part returns the default value of the argument with the "idPart".
+    pub fn default_for_id_part(&self, id_part: &str) -> Option<&String> {
+        match id_part {
+            "subscription" => self.defaults.subscription.as_ref(),
+            "resource_group" => self.defaults.resource_group.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Config {
+    pub fn dir() -> Option<PathBuf> {
+        if let Some(dir) = std::env::var_os(CONFIG_DIR_ENV) {
+            return Some(PathBuf::from(dir));
+        }
+        dirs::home_dir().map(|home| home.join(".config").join("az-rs"))
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        Self::dir().map(|dir| dir.join("config.toml"))
+    }
+
+    // load reads the config file, or returns the default config if the file doesn't exist.
+    pub fn load() -> Result<Self> {
+        use anyhow::Context;
+
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content =
+            std::fs::read_to_string(&path).context(format!("reading config file {path:?}"))?;
+        Self::from_toml(&content).context(format!("parsing config file {path:?}"))
+    }
+
+    // save writes the config file, creating its parent directory if not exists.
+    pub fn save(&self) -> Result<()> {
+        use anyhow::{Context, anyhow};
+
+        let path = Self::path().ok_or(anyhow!("failed to determine the config file path"))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context(format!("creating config directory {dir:?}"))?;
+        }
+        std::fs::write(&path, self.to_toml()?).context(format!("writing config file {path:?}"))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Config {
+    // There is no config file on wasm32.
+    pub fn load() -> Result<Self> {
+        Ok(Self::default())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        bail!("saving the config is not supported on wasm32")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn from_toml() {
+        let config = Config::from_toml(
+            r#"
+cloud = "local"
+
+[clouds.local]
+arm_endpoint = "http://localhost:8080"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.cloud, Some("local".to_string()));
+        assert_eq!(
+            config.clouds.get("local"),
+            Some(&CloudSpec {
+                arm_endpoint: "http://localhost:8080".to_string(),
+                audience: None,
+                authority_host: None,
+            })
+        );
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn set_get_unset() {
+        let mut config = Config::default();
+        config.set(KEY_DEFAULTS_RESOURCE_GROUP, "rg").unwrap();
+        config.set(KEY_CLOUD, "AzureChinaCloud").unwrap();
+        assert_eq!(
+            config.get(KEY_DEFAULTS_RESOURCE_GROUP).unwrap(),
+            Some("rg".to_string())
+        );
+        assert_eq!(
+            config.default_for_id_part("resource_group"),
+            Some(&"rg".to_string())
+        );
+        assert_eq!(config.get(KEY_DEFAULTS_SUBSCRIPTION).unwrap(), None);
+        assert_eq!(
+            config.to_toml().unwrap(),
+            "cloud = \"AzureChinaCloud\"\n\n[defaults]\nresource_group = \"rg\"\n"
+        );
+
+        config.unset(KEY_DEFAULTS_RESOURCE_GROUP).unwrap();
+        assert_eq!(config.get(KEY_DEFAULTS_RESOURCE_GROUP).unwrap(), None);
+        assert_eq!(config.to_toml().unwrap(), "cloud = \"AzureChinaCloud\"\n");
+
+        assert!(config.set("foo", "bar").is_err());
+        assert!(config.get("foo").is_err());
+    }
+}
@@ -1,15 +1,24 @@
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use api::ApiManager;
 use arg::CliInput;
+use auth::Auth;
 use azure_core::credentials::TokenCredential;
 use clap::{ArgMatches, Command};
-use std::{path::PathBuf, sync::Arc};
+use client::policies::SecretProps;
+use client::poller::{Progress, ProgressFunc};
+use client::{Client, NewClientOptions, RunOptions};
+use cloud::Cloud;
+use config::Config;
+use std::{path::PathBuf, rc::Rc, sync::Arc};
 
 pub mod api;
 pub mod arg;
+pub mod auth;
 pub mod azidentityext;
 pub mod client;
+pub mod cloud;
 pub mod cmd;
+pub mod config;
 pub mod log;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -18,20 +27,34 @@ pub mod lsp;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm_exports;
 
-pub async fn run<CF, RF>(
+// The suffix appended to the user agent of every request, e.g. to identify the caller's platform.
+pub const USER_AGENT_ENV: &str = "AZURE_HTTP_USER_AGENT";
+
+pub async fn run<CF, RF, PF>(
     metadata_path: PathBuf,
     raw_input: Vec<String>,
     cred_func: CF,
     mut resp_func: RF,
+    progress_func: PF,
 ) -> Result<()>
 where
-    CF: FnOnce() -> Result<Arc<dyn TokenCredential>>,
+    CF: Fn(&Cloud, &Auth) -> Result<Arc<dyn TokenCredential>>,
     RF: FnMut(String) -> (),
+    PF: Fn(&Progress) + 'static,
 {
+    let progress_func: ProgressFunc = Rc::new(progress_func);
+
     tracing::info!("Running CLI with input: {:?}", raw_input);
     let matches = get_matches(cmd::cmd(), raw_input.clone())?;
 
+    // The config file is only loaded by the subcommands that use it, so that a malformed one
+    // doesn't break the others.
     match matches.subcommand() {
+        // The "config" subcommand deals with a malformed config file by itself.
+        Some(("config", matches)) => {
+            return run_config(matches, Config::load(), resp_func);
+        }
+
         #[cfg(not(target_arch = "wasm32"))]
         Some(("lsp", _)) => {
             lsp::serve().await;
@@ -39,6 +62,32 @@ where
             return Ok(());
         }
 
+        Some(("lro", matches)) => {
+            let config = Config::load()?;
+            let cloud = Cloud::resolve(
+                matches
+                    .get_one::<String>(cmd::CLOUD_OPTION)
+                    .map(|v| v.as_str()),
+                &config,
+            )?;
+            let auth = resolve_auth(matches, &config)?;
+            let cred = cred_func(&cloud, &auth)?;
+            // The operation (thus its schema) of the LRO is unknown, the well known secret
+            // properties are redacted from the debug log instead.
+            let client_options = NewClientOptions {
+                secret_props: SecretProps::WellKnown,
+                ..new_client_options(matches, &cloud, &auth, &cred_func)?
+            };
+            let client = Client::new(
+                &cloud.arm_endpoint,
+                vec![cloud.scope()],
+                cred,
+                Some(client_options),
+            )?;
+            run_lro(matches, &client, resp_func, progress_func).await?;
+            return Ok(());
+        }
+
         Some(("api", matches)) => {
             let args = if let Some(args) = matches.get_many::<String>("args") {
                 args.cloned().collect()
@@ -46,8 +95,9 @@ where
                 vec![]
             };
             let args = CliInput::new(args)?;
+            let config = Config::load()?;
             let api_manager = ApiManager::new(&metadata_path)?;
-            let cmd = cmd::cmd_api(&api_manager, &args);
+            let cmd = cmd::cmd_api(&api_manager, &args, &config);
             let mut matches = get_matches(cmd, raw_input.clone())?;
 
             // Reaches here indicates an API command/operation is specified.
@@ -59,8 +109,27 @@ where
                 matches = m.clone();
             }
 
+            let cloud = Cloud::resolve(
+                matches
+                    .get_one::<String>(cmd::CLOUD_OPTION)
+                    .map(|v| v.as_str()),
+                &config,
+            )?;
+            let auth = resolve_auth(&matches, &config)?;
+            let client_options = new_client_options(&matches, &cloud, &auth, &cred_func)?;
+
             api_manager
-                .run(&subcommands, &args, &matches, cred_func, resp_func)
+                .run(
+                    &subcommands,
+                    &args,
+                    &matches,
+                    &cloud,
+                    &config,
+                    client_options,
+                    |cloud: &Cloud| cred_func(cloud, &auth),
+                    resp_func,
+                    Some(progress_func),
+                )
                 .await?;
             return Ok(());
         }
@@ -68,6 +137,132 @@ where
     }
 }
 
+fn resolve_auth(matches: &ArgMatches, config: &Config) -> Result<Auth> {
+    let aux_tenants: Vec<String> = matches
+        .get_many::<String>(cmd::AUX_TENANTS_OPTION)
+        .map(|v| v.cloned().collect())
+        .unwrap_or_default();
+    Auth::resolve(
+        matches
+            .get_one::<String>(cmd::AUTH_MODE_OPTION)
+            .map(|v| v.as_str()),
+        &aux_tenants,
+        config,
+    )
+}
+
+// new_client_options builds the client options, including the credentials of the auxiliary tenants
+// from the same credential chain, and the correlation id shared by all the requests of this command.
+fn new_client_options<CF>(
+    matches: &ArgMatches,
+    cloud: &Cloud,
+    auth: &Auth,
+    cred_func: &CF,
+) -> Result<NewClientOptions>
+where
+    CF: Fn(&Cloud, &Auth) -> Result<Arc<dyn TokenCredential>>,
+{
+    let aux_credentials = auth
+        .aux_tenants
+        .iter()
+        .map(|tenant| cred_func(cloud, &auth.for_aux_tenant(tenant)))
+        .collect::<Result<Vec<_>>>()?;
+    let headers = matches
+        .get_many::<String>(cmd::HEADER_OPTION)
+        .into_iter()
+        .flatten()
+        .map(|header| {
+            let (k, v) = header
+                .split_once('=')
+                .filter(|(k, _)| !k.trim().is_empty())
+                .ok_or(anyhow!(r#"expect "KEY=VALUE", got {header:?}"#))?;
+            // The header names are case insensitive, while the SDK expects them in lower case.
+            Ok((k.trim().to_ascii_lowercase(), v.to_string()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let debug = matches.get_flag(cmd::DEBUG_OPTION);
+    if debug {
+        log::enable_debug();
+    }
+    Ok(NewClientOptions {
+        aux_credentials,
+        debug,
+        correlation_id: Some(uuid::Uuid::new_v4().to_string()),
+        headers,
+        user_agent_suffix: std::env::var(USER_AGENT_ENV)
+            .ok()
+            .filter(|v| !v.trim().is_empty()),
+        ..Default::default()
+    })
+}
+
+// run_config runs the "config" subcommand. A malformed config file is never overwritten, i.e.
+// "set" and "unset" fail until it is fixed, while "get" reads the defaults with a warning.
+fn run_config<RF>(matches: &ArgMatches, config: Result<Config>, mut resp_func: RF) -> Result<()>
+where
+    RF: FnMut(String) -> (),
+{
+    let writable = |config: Result<Config>| {
+        config.context("the config file must be fixed or removed before it can be updated")
+    };
+    match matches.subcommand() {
+        Some(("set", matches)) => {
+            let mut config = writable(config)?;
+            let key = matches.get_one::<String>("key").unwrap();
+            let value = matches.get_one::<String>("value").unwrap();
+            config.set(key, value)?;
+            config.save()?;
+        }
+        Some(("get", matches)) => {
+            let config = config.unwrap_or_else(|e| {
+                eprintln!("Warning: ignoring the config file: {e:#}");
+                Config::default()
+            });
+            match matches.get_one::<String>("key") {
+                Some(key) => resp_func(config.get(key)?.unwrap_or_default()),
+                None => resp_func(config.to_toml()?),
+            }
+        }
+        Some(("unset", matches)) => {
+            let mut config = writable(config)?;
+            let key = matches.get_one::<String>("key").unwrap();
+            config.unset(key)?;
+            config.save()?;
+        }
+        _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    }
+    Ok(())
+}
+
+async fn run_lro<RF>(
+    matches: &ArgMatches,
+    client: &Client,
+    mut resp_func: RF,
+    progress_func: ProgressFunc,
+) -> Result<()>
+where
+    RF: FnMut(String) -> (),
+{
+    match matches.subcommand() {
+        Some(("wait", matches)) => {
+            let token = matches.get_one::<String>("token").unwrap();
+            let options = RunOptions {
+                poll_options: Some(cmd::poll_until_done_options(matches, Some(progress_func))),
+                ..Default::default()
+            };
+            let response = client.resume(token, Some(options)).await?;
+            resp_func(String::from_utf8(response.body.to_vec())?);
+        }
+        Some(("show", matches)) => {
+            let token = matches.get_one::<String>("token").unwrap();
+            let state = client.poll_once(token, None).await?;
+            resp_func(serde_json::to_string(&state)?);
+        }
+        _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    }
+    Ok(())
+}
+
 #[cfg(target_arch = "wasm32")]
 fn get_matches(cmd: Command, input: Vec<String>) -> Result<ArgMatches> {
     use anyhow::anyhow;
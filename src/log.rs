@@ -1,16 +1,32 @@
 static INIT: std::sync::Once = std::sync::Once::new();
 
+// The filter of the native subscriber, which is reloaded by "--debug".
+#[cfg(not(target_arch = "wasm32"))]
+static FILTER: std::sync::OnceLock<
+    tracing_subscriber::reload::Handle<tracing_subscriber::EnvFilter, tracing_subscriber::Registry>,
+> = std::sync::OnceLock::new();
+
+// The directive that enables the debug log of the HTTP requests and responses.
+#[cfg(not(target_arch = "wasm32"))]
+const DEBUG_DIRECTIVE: &str = "az_rs::client::policies=debug";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn env_filter() -> String {
+    std::env::var("AZURE_LOG").unwrap_or_else(|_| "off".to_string())
+}
+
 #[allow(dead_code)]
 #[cfg(not(target_arch = "wasm32"))]
 fn init_tracing_subscriber() {
     use std::io;
     use std::{env, fs};
-    use tracing_subscriber::EnvFilter;
+    use tracing_subscriber::{EnvFilter, prelude::*, reload};
 
-    let filter = EnvFilter::try_from_env("AZURE_LOG").unwrap_or_else(|_| EnvFilter::from("off"));
-    let b = tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_ansi(false);
+    let filter = EnvFilter::try_new(env_filter()).unwrap_or_else(|_| EnvFilter::new("off"));
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = FILTER.set(handle);
+    let registry = tracing_subscriber::registry().with(filter);
+    let fmt_layer = tracing_subscriber::fmt::layer().with_ansi(false);
 
     match env::var("AZURE_LOG_PATH") {
         Ok(p) => {
@@ -19,14 +35,30 @@ fn init_tracing_subscriber() {
                 .create(true)
                 .open(p)
                 .expect("open log file");
-            b.with_writer(f).init();
+            registry.with(fmt_layer.with_writer(f)).init();
         }
         Err(_) => {
-            b.with_writer(io::stderr).init();
+            registry.with(fmt_layer.with_writer(io::stderr)).init();
         }
     }
 }
 
+// enable_debug raises the level of the debug log of the HTTP requests and responses, on top of
+// the "AZURE_LOG" filter.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn enable_debug() {
+    use tracing_subscriber::EnvFilter;
+
+    let Some(handle) = FILTER.get() else {
+        return;
+    };
+    let filter = EnvFilter::try_new(format!("{},{DEBUG_DIRECTIVE}", env_filter()))
+        .unwrap_or_else(|_| EnvFilter::new(DEBUG_DIRECTIVE));
+    if let Err(e) = handle.reload(filter) {
+        tracing::warn!("enabling the debug log: {e}");
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 fn init_tracing_subscriber() {
     use tracing_subscriber::prelude::*;
@@ -40,6 +72,11 @@ fn init_tracing_subscriber() {
     tracing_subscriber::registry().with(fmt_layer).init();
 }
 
+// enable_debug is a no-op for the WASM module, whose subscriber logs all the levels to the web
+// console.
+#[cfg(target_arch = "wasm32")]
+pub fn enable_debug() {}
+
 pub fn set_global_logger() {
     INIT.call_once(|| {
         init_tracing_subscriber();
@@ -1,4 +1,8 @@
-use crate::azidentityext::access_token_credential::AccessTokenCredential;
+use crate::auth::Auth;
+use crate::azidentityext::access_token_credential::{AccessTokenCredential, TokenRefresher};
+use crate::client::poller::Progress;
+use crate::cloud::Cloud;
+use crate::cmd::AUX_TENANTS_OPTION;
 use crate::log::set_global_logger;
 use crate::run;
 use azure_core::credentials::TokenCredential;
@@ -8,12 +12,32 @@ use std::sync::Arc;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
-pub async fn run_cli(args: Vec<String>, token: &str) -> Result<String, JsValue> {
+pub async fn run_cli(
+    args: Vec<String>,
+    token: &str,
+    progress: Option<js_sys::Function>,
+    refresh: Option<js_sys::Function>,
+) -> Result<String, JsValue> {
     console_error_panic_hook::set_once();
     set_global_logger();
 
-    let cred_func = || -> anyhow::Result<Arc<dyn TokenCredential>> {
-        let cred = AccessTokenCredential::new(token.to_string())?;
+    // The token is always provided by the web UI, regardless of the auth mode. It is only for the
+    // signed in tenant, hence the auxiliary tenants are not supported. As there is no config file
+    // in the web UI, they can only be specified by the option, i.e. the auth is resolved from the
+    // parsed "--aux-tenants".
+    let cred_func = |_: &Cloud, auth: &Auth| -> anyhow::Result<Arc<dyn TokenCredential>> {
+        if !auth.aux_tenants.is_empty() {
+            anyhow::bail!(
+                "the auxiliary tenants (--{AUX_TENANTS_OPTION}) are not supported in the web UI"
+            );
+        }
+        let cred = match &refresh {
+            Some(refresh) => AccessTokenCredential::with_refresher(
+                token.to_string(),
+                Arc::new(JsTokenRefresher(refresh.clone())),
+            )?,
+            None => AccessTokenCredential::new(token.to_string())?,
+        };
         Ok(cred)
     };
 
@@ -21,12 +45,62 @@ pub async fn run_cli(args: Vec<String>, token: &str) -> Result<String, JsValue>
     let resp_func = |res: String| {
         resp = res;
     };
-    run(PathBuf::new(), args, cred_func, resp_func)
+    // The progress is forwarded to the JS callback (if any) as a JSON string.
+    let progress_func = move |p: &Progress| {
+        if let Some(progress) = &progress {
+            let p = serde_json::json!({
+                "status": p.status.to_string(),
+                "elapsed": p.elapsed.as_secs(),
+                "nextPoll": p.next_poll.as_secs(),
+            });
+            if let Err(e) = progress.call1(&JsValue::NULL, &JsValue::from_str(&p.to_string())) {
+                tracing::warn!("calling the progress callback: {e:?}");
+            }
+        }
+    };
+    run(PathBuf::new(), args, cred_func, resp_func, progress_func)
         .await
         .map_err(jsfy)?;
     Ok(resp)
 }
 
+// JsTokenRefresher refreshes the access token by calling the JS callback, which returns the new
+// token, or a promise of it.
+#[derive(Debug)]
+struct JsTokenRefresher(js_sys::Function);
+
+#[cfg(target_feature = "atomics")]
+compile_error!("JsTokenRefresher is only Send and Sync without the \"atomics\" target feature");
+
+// SAFETY: The JS function lives in the JS heap of the thread that created it, hence isn't Send nor
+// Sync. This module is only built for wasm32 (see lib.rs) without the "atomics" target feature, where
+// there is only the one JS thread, i.e. the refresher is never sent to or shared with another thread.
+unsafe impl Send for JsTokenRefresher {}
+// SAFETY: See the Send impl above.
+unsafe impl Sync for JsTokenRefresher {}
+
+#[async_trait::async_trait(?Send)]
+impl TokenRefresher for JsTokenRefresher {
+    async fn refresh(&self) -> azure_core::Result<String> {
+        let error = |e: JsValue| {
+            azure_core::Error::message(
+                azure_core::error::ErrorKind::Credential,
+                format!("calling the refresh callback: {e:?}"),
+            )
+        };
+        let mut value = self.0.call0(&JsValue::NULL).map_err(error)?;
+        if value.has_type::<js_sys::Promise>() {
+            value = wasm_bindgen_futures::JsFuture::from(js_sys::Promise::from(value))
+                .await
+                .map_err(error)?;
+        }
+        value.as_string().ok_or(azure_core::Error::message(
+            azure_core::error::ErrorKind::Credential,
+            "the refresh callback didn't return a token",
+        ))
+    }
+}
+
 fn jsfy<E>(e: E) -> JsValue
 where
     E: Debug,
@@ -109,7 +109,22 @@ export const AzureCLIInterface: React.FC = () => {
       const result = await wasmModule.run_cli(
         args,
 
-        accessToken // Using access token instead of client secret
+        accessToken, // Using access token instead of client secret
+        // Show the progress of the long running operation, until its result replaces it
+        (progress: string) => {
+          const { status, elapsed, nextPoll } = JSON.parse(progress);
+          setOutput(
+            `Executing Azure CLI command... (status: ${status}, elapsed: ${elapsed}s, next poll in ${nextPoll}s)`
+          );
+        },
+        // Refresh the access token once it is about to expire, e.g. during a long running operation
+        async () => {
+          const token = await getAzureManagementToken();
+          if (!token) {
+            throw new Error('Failed to refresh access token');
+          }
+          return token;
+        }
       );
 
       // Try to format JSON if the result is valid JSON
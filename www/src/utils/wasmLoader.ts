@@ -1,6 +1,11 @@
 // WASM module types
 export interface WasmModule {
-  run_cli: (args: string[],  token: string) => Promise<string>;
+  run_cli: (
+    args: string[],
+    token: string,
+    progress?: (progress: string) => void,
+    refresh?: () => Promise<string>,
+  ) => Promise<string>;
 }
 
 // Simple cache to avoid re-initializing